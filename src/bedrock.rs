@@ -0,0 +1,162 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use tokio::time::timeout;
+use crate::connection::connect_udp;
+#[cfg(feature = "resolve")]
+use std::sync::Arc;
+#[cfg(feature = "resolve")]
+use crate::resolver::{Resolver, SystemResolver};
+use crate::mc_text::BedrockStatus;
+use crate::packets::{UnconnectedPing, UnconnectedPong};
+
+/// Largest datagram accepted from the server.
+const MAX_DATAGRAM_LEN: usize = 1500;
+
+/// Represents a Bedrock Edition pinger using the RakNet unconnected ping over UDP.
+///
+/// Mirrors [`Connection`](crate::connection::Connection): it is created with the
+/// target address and configured with the same builder-style methods. There is
+/// no connect step, each [`ping`](BedrockConnection::ping) sends a single datagram.
+///
+/// # Fields
+///
+/// * `timeout`: Optional timeout duration in milliseconds for the whole exchange.
+/// * `addr`: Target Bedrock server address `(host, port)`, usually port 19132.
+/// * `client_guid`: RakNet client GUID sent in the ping, random by default.
+/// * `resolver`: Resolves host names, the system resolver unless set ("resolve" feature).
+pub struct BedrockConnection {
+    pub is_initialized: bool,
+    pub timeout: Option<u64>,
+    pub addr: (String, u16),
+    pub client_guid: i64,
+    #[cfg(feature = "resolve")]
+    pub resolver: Arc<dyn Resolver>,
+}
+
+impl BedrockConnection {
+    /// Creates a new `BedrockConnection` with the target server address.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mc_ping::bedrock::BedrockConnection;
+    ///
+    /// let conn = BedrockConnection::new(("127.0.0.1".to_string(), 19132));
+    /// ```
+    pub fn new(addr: (String, u16)) -> Self {
+        Self {
+            is_initialized: true,
+            timeout: None,
+            addr,
+            client_guid: RandomState::new().build_hasher().finish() as i64,
+            #[cfg(feature = "resolve")]
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+
+    /// Sets the timeout for the ping exchange (milliseconds).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use mc_ping::bedrock::BedrockConnection;
+    ///
+    /// let conn = BedrockConnection::new(("127.0.0.1".to_string(), 19132)).timeout(5000)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timeout(mut self, timeout: u64) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: BedrockConnection::new((addr, port)).timeout(u64)"));
+        }
+
+        self.timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// Sets the resolver used for host names, e.g. one shared with other connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use std::sync::Arc;
+    /// use mc_ping::bedrock::BedrockConnection;
+    /// use mc_ping::resolver::{CachingResolver, SystemResolver};
+    ///
+    /// let resolver = Arc::new(CachingResolver::new(SystemResolver));
+    /// let conn = BedrockConnection::new(("example.com".to_string(), 19132)).resolver(resolver)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "resolve")]
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: BedrockConnection::new((addr, port)).resolver(Arc<dyn Resolver>)"));
+        }
+
+        self.resolver = resolver;
+        Ok(self)
+    }
+
+    /// Sends an Unconnected Ping and parses the Unconnected Pong.
+    ///
+    /// Datagrams that are not a pong for this ping are ignored until the timeout
+    /// (8000 ms by default) expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if resolution, sending or receiving fails, the timeout
+    /// expires, or the status string is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::bedrock::BedrockConnection;
+    ///
+    /// let conn = BedrockConnection::new(("127.0.0.1".to_string(), 19132)).timeout(5000)?;
+    /// let status = conn.ping().await?;
+    /// println!("{} ({}/{})", status.motd, status.online, status.max);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ping(&self) -> Result<BedrockStatus> {
+        let _timeout = self.timeout.unwrap_or(8000);
+
+        timeout(Duration::from_millis(_timeout), async {
+            #[cfg(feature = "resolve")]
+            let socket = connect_udp(&self.addr, &*self.resolver).await?;
+            #[cfg(not(feature = "resolve"))]
+            let socket = connect_udp(&self.addr).await?;
+
+            let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+            let ping = UnconnectedPing::new(time, self.client_guid);
+            socket.send(&ping.to_bytes()).await?;
+
+            let mut buf = [0u8; MAX_DATAGRAM_LEN];
+            loop {
+                let n = socket.recv(&mut buf).await?;
+                match UnconnectedPong::from(&buf[..n]) {
+                    Ok(pong) if pong.time == time => return pong.parse_status(),
+                    _ => continue,
+                }
+            }
+        }).await?
+    }
+}
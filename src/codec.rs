@@ -0,0 +1,139 @@
+//! [`tokio_util::codec`] support for Minecraft frames.
+//!
+//! [`MinecraftCodec`] turns a byte stream into `(packet_id, payload)` items
+//! and back, using the same framing as [`Connection`](crate::connection::Connection)
+//! (which uses it internally): a VarInt length prefix, plus the data length
+//! and zlib compression once a threshold is set.
+//!
+//! Wrapping a stream in `Framed::new(stream, MinecraftCodec::new())` gives a
+//! `Stream` of received packets and a `Sink` to send them.
+//!
+//! # Example
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use mc_ping::codec::MinecraftCodec;
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = MinecraftCodec::new();
+//! let mut buf = BytesMut::new();
+//! codec.encode((0x01, 42i64.to_be_bytes()), &mut buf)?;
+//! assert_eq!(&buf[..2], &[0x09, 0x01]);
+//!
+//! let (packet_id, payload) = codec.decode(&mut buf)?.unwrap();
+//! assert_eq!(packet_id, 0x01);
+//! assert_eq!(payload[..], 42i64.to_be_bytes());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::io;
+use anyhow::{anyhow, Context, Result};
+use bytes::{Buf, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+use crate::connection::MAX_PACKET_LEN;
+use crate::framing::{decode_frame, encode_frame};
+use crate::varint::VarInt;
+
+/// Frames Minecraft packets for [`tokio_util::codec::Framed`].
+///
+/// Decodes into `(packet_id, payload)`, where the payload is the packet data
+/// after the id, and encodes `(packet_id, payload)` for any payload that is
+/// `AsRef<[u8]>`.
+#[derive(Debug, Clone)]
+pub struct MinecraftCodec {
+    compression_threshold: Option<usize>,
+    max_frame_len: usize,
+}
+
+impl MinecraftCodec {
+    /// Creates an uncompressed codec accepting frames up to [`MAX_PACKET_LEN`].
+    pub fn new() -> MinecraftCodec {
+        MinecraftCodec {
+            compression_threshold: None,
+            max_frame_len: MAX_PACKET_LEN,
+        }
+    }
+
+    /// Sets the largest frame accepted or produced, not counting its length prefix.
+    pub fn max_frame_len(mut self, max_frame_len: usize) -> MinecraftCodec {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Switches to compressed framing, as requested by Set Compression.
+    ///
+    /// A negative threshold disables compression.
+    pub fn set_compression(&mut self, threshold: i32) {
+        self.compression_threshold = usize::try_from(threshold).ok();
+    }
+
+    /// Returns the compression threshold, `None` while uncompressed.
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    /// Takes one frame off the front of `src` and returns its body (packet id
+    /// followed by data), or `None` if the frame is not complete yet.
+    pub(crate) fn decode_body(&mut self, src: &mut BytesMut) -> Result<Option<Vec<u8>>> {
+        let (len, header) = match VarInt::decode_slice(src) {
+            Ok((len, header)) => (i32::from(len), header),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err).context("Failed to read packet length"),
+        };
+        if len <= 0 || len as usize > self.max_frame_len {
+            return Err(anyhow!("Invalid packet length: {}", len));
+        }
+
+        let frame_len = header + len as usize;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+        src.advance(header);
+        let contents = src.split_to(len as usize);
+        decode_frame(&contents, self.compression_threshold).map(Some)
+    }
+
+    /// Appends `body` (packet id followed by data) to `dst` as one frame.
+    pub(crate) fn encode_body(&mut self, body: &[u8], dst: &mut BytesMut) -> Result<()> {
+        let frame = encode_frame(body, self.compression_threshold)?;
+        let len = i32::from(VarInt::decode_slice(&frame)?.0) as usize;
+        if len > self.max_frame_len {
+            return Err(anyhow!("Packet of {} bytes exceeds the maximum frame length {}", len, self.max_frame_len));
+        }
+        dst.extend_from_slice(&frame);
+        Ok(())
+    }
+}
+
+impl Default for MinecraftCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for MinecraftCodec {
+    type Item = (i32, Bytes);
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<(i32, Bytes)>> {
+        let Some(body) = self.decode_body(src)? else {
+            return Ok(None);
+        };
+        let (packet_id, id_len) = VarInt::decode_slice(&body).context("Failed to read packet id")?;
+        Ok(Some((i32::from(packet_id), Bytes::from(body).slice(id_len..))))
+    }
+}
+
+impl<B: AsRef<[u8]>> Encoder<(i32, B)> for MinecraftCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, (packet_id, payload): (i32, B), dst: &mut BytesMut) -> Result<()> {
+        let payload = payload.as_ref();
+        let packet_id = VarInt::from(packet_id);
+        let mut body = Vec::with_capacity(packet_id.size() + payload.len());
+        body.extend_from_slice(packet_id.as_bytes());
+        body.extend_from_slice(payload);
+        self.encode_body(&body, dst)
+    }
+}
@@ -1,36 +1,253 @@
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
-use std::time::Duration;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, AsyncReadExt};
+use bytes::BytesMut;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::net::SocketAddr;
+use tokio::net::{TcpStream, UdpSocket};
 use tokio::time::timeout;
+#[cfg(feature = "encryption")]
+use crate::encryption::{encrypt_with_public_key, Encryption};
+#[cfg(feature = "encryption")]
+use crate::packets::EncryptionResponse;
+use crate::codec::MinecraftCodec;
 use crate::mc_text::ServerStatus;
-use crate::packets::{ClientHandshake, ServerQueryResponse, StatusQuery};
+use crate::packets::{validate_protocol_version, ClientHandshake, Intent, LegacyPing, LegacyPingResponse, LegacyPingVariant, LoginResponse, LoginStart, Packet, LEGACY_VERSION_NAME, UNKNOWN_PROTOCOL_VERSION, PingRequest, PongResponse, ServerQueryResponse, StatusQuery, DEFAULT_PROTOCOL_VERSION};
 use anyhow::{anyhow, Result};
-use tokio::net::lookup_host;
-use tokio_socks::tcp::Socks5Stream;
+#[cfg(feature = "resolve")]
+use std::sync::Arc;
+#[cfg(feature = "resolve")]
+use crate::resolver::{Resolver, SystemResolver};
+#[cfg(feature = "resolve")]
+use std::net::IpAddr;
+#[cfg(feature = "resolve")]
+use tokio::task::JoinSet;
+#[cfg(feature = "resolve")]
+use tokio::time::timeout_at;
+#[cfg(feature = "resolve")]
+use crate::dns::{order_srv_records, DEFAULT_PORT};
+use crate::proxy::{connect_through, Proxy};
 
+/// Largest packet the server is allowed to send (2^21 - 1 bytes, the protocol limit).
+pub const MAX_PACKET_LEN: usize = 2_097_151;
+
+#[cfg(not(feature = "resolve"))]
 fn is_domain(addr: &str) -> bool {
     addr.parse::<std::net::IpAddr>().is_err()
 }
 
-/// Represents a TCP connection to a Minecraft server.
+/// Returns true if `err` means the peer closed or reset the connection.
+fn is_rejection(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|e| matches!(
+        e.kind(),
+        std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::UnexpectedEof
+    ))
+}
+
+/// Delay between starting connection attempts to successive addresses, the
+/// value recommended by RFC 8305 (Happy Eyeballs v2).
+#[cfg(feature = "resolve")]
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Orders resolved addresses for connecting, alternating between address
+/// families and starting with the family of the first address (RFC 8305,
+/// section 4). The relative order within each family is kept.
+#[cfg(feature = "resolve")]
+pub(crate) fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_ipv6 = first.is_ipv6();
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs.into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    preferred.reverse();
+    other.reverse();
+    while !preferred.is_empty() || !other.is_empty() {
+        ordered.extend(preferred.pop());
+        ordered.extend(other.pop());
+    }
+    ordered
+}
+
+/// Races TCP connections to `addrs` in the style of RFC 8305 (Happy Eyeballs).
+///
+/// Addresses are tried in the order given, a new attempt starting every
+/// `attempt_delay` or as soon as the previous one fails, while earlier
+/// attempts keep running. The first established connection wins and the
+/// remaining attempts are cancelled. Returns the stream and the address it
+/// is connected to.
+///
+/// # Errors
+///
+/// Returns the last connection error if every attempt fails, or an error if
+/// `addrs` is empty.
+#[cfg(feature = "resolve")]
+pub(crate) async fn connect_happy_eyeballs(addrs: Vec<SocketAddr>, attempt_delay: Duration) -> Result<(TcpStream, SocketAddr)> {
+    let mut pending = addrs.into_iter();
+    // Dropping the set aborts the attempts still running
+    let mut attempts = JoinSet::new();
+    let mut last_err = None;
+
+    loop {
+        if let Some(addr) = pending.next() {
+            attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+        }
+
+        // Start the next attempt once one fails or the delay is over
+        let more_pending = pending.len() > 0;
+        tokio::select! {
+            joined = attempts.join_next() => match joined {
+                Some(Ok((addr, Ok(stream)))) => return Ok((stream, addr)),
+                Some(Ok((addr, Err(e)))) => last_err = Some(anyhow!("Failed to connect to {}: {}", addr, e)),
+                Some(Err(e)) => return Err(e.into()),
+                None => return Err(last_err.unwrap_or_else(|| anyhow!("No address to connect to"))),
+            },
+            _ = tokio::time::sleep(attempt_delay), if more_pending => {}
+        }
+    }
+}
+
+/// Binds a UDP socket and connects it to `addr`, for the UDP based protocols
+/// (Bedrock ping, Query).
+///
+/// Domain names need the "resolve" feature, like [`Connection::connect`], and
+/// are resolved through `resolver`.
+pub(crate) async fn connect_udp(
+    addr: &(String, u16),
+    #[cfg(feature = "resolve")] resolver: &dyn Resolver,
+) -> Result<UdpSocket> {
+    #[cfg(not(feature = "resolve"))]
+    let target = {
+        let ip = addr.0.parse::<std::net::IpAddr>()
+            .map_err(|_| anyhow!(r#"Enable feature "resolve" to enable domain resolving"#))?;
+        SocketAddr::new(ip, addr.1)
+    };
+
+    #[cfg(feature = "resolve")]
+    let target = match addr.0.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, addr.1),
+        Err(_) => {
+            let ip = resolver.resolve(&addr.0).await?.addrs.into_iter().next()
+                .ok_or_else(|| anyhow!("Could not resolve address: {}", addr.0))?;
+            SocketAddr::new(ip, addr.1)
+        }
+    };
+
+    let bind_addr: SocketAddr = if target.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(target).await?;
+    Ok(socket)
+}
+
+/// Protocol variant that answered a [`Connection::probe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVariant {
+    /// Modern (1.7+) handshake and status exchange.
+    Modern,
+    /// Legacy (pre-Netty) server list ping.
+    Legacy(LegacyPingVariant),
+}
+
+/// How a server reacted to a [`Connection::login_probe`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoginProbe {
+    /// The server sent an Encryption Request: online-mode (premium accounts only).
+    OnlineMode {
+        /// Server id, empty on modern servers.
+        server_id: String,
+        /// Server public key, DER encoded.
+        public_key: Vec<u8>,
+        /// Random token to encrypt with the public key.
+        verify_token: Vec<u8>,
+        /// Whether the server asks the client to authenticate with the session server.
+        should_authenticate: bool,
+    },
+    /// The server sent Set Compression or Login Success without encryption: offline-mode.
+    OfflineMode {
+        /// Compression threshold, if Set Compression came first.
+        compression_threshold: Option<i32>,
+    },
+    /// The server disconnected the client, e.g. whitelist or version mismatch.
+    Disconnected {
+        /// Disconnect reason as a JSON text component.
+        reason: serde_json::Value,
+    },
+    /// The server sent a Login Plugin Request: usually a proxy (Velocity) or mod loader.
+    PluginRequest {
+        /// Plugin channel, e.g. "velocity:player_info".
+        channel: String,
+    },
+}
+
+impl LoginProbe {
+    /// Returns true if the server let the client continue the login,
+    /// i.e. it did not disconnect it right away.
+    pub fn is_accepted(&self) -> bool {
+        !matches!(self, LoginProbe::Disconnected { .. })
+    }
+}
+
+impl From<LoginResponse> for LoginProbe {
+    fn from(response: LoginResponse) -> Self {
+        match response {
+            LoginResponse::Disconnect { reason } => LoginProbe::Disconnected { reason },
+            LoginResponse::EncryptionRequest { server_id, public_key, verify_token, should_authenticate } => {
+                LoginProbe::OnlineMode { server_id, public_key, verify_token, should_authenticate }
+            }
+            LoginResponse::LoginSuccess { .. } => LoginProbe::OfflineMode { compression_threshold: None },
+            LoginResponse::SetCompression { threshold } => {
+                LoginProbe::OfflineMode { compression_threshold: Some(threshold) }
+            }
+            LoginResponse::PluginRequest { channel, .. } => LoginProbe::PluginRequest { channel },
+        }
+    }
+}
+
+/// Represents a connection to a Minecraft server.
 /// Supports optional SOCKS5 proxy connections.
 ///
 /// # Type Parameters
 ///
-/// * `T`: Underlying TCP stream type, usually `TcpStream`.
+/// * `T`: Underlying stream type, usually `TcpStream`. Any `AsyncRead + AsyncWrite + Unpin`
+///   stream can be wrapped with [`Connection::from_stream`].
 ///
 /// # Fields
 ///
 /// * `stream`: Optionally holds the active TCP stream.
 /// * `timeout`: Optional timeout duration in milliseconds for connection and I/O.
-/// * `proxy_addr`: Optional SOCKS5 proxy address as `(host, port)`.
+/// * `proxy`: Optional SOCKS4, SOCKS4a or SOCKS5 proxy to connect through.
 /// * `addr`: Target Minecraft server address `(host, port)`.
+/// * `protocol_version`: Protocol version sent in the handshake (-1 = unknown).
+/// * `intent`: Next state requested by the handshake.
+/// * `codec`: Packet framing, compressed once Set Compression was received.
+/// * `encryption`: AES/CFB8 state once encryption was enabled ("encryption" feature).
+/// * `nameserver`: DNS server for SRV lookups, `/etc/resolv.conf` if unset ("resolve" feature).
+/// * `resolver`: Resolves host names, the system resolver unless set ("resolve" feature).
+/// * `peer_addr`: Address the stream is connected to once connected (the proxy's, if one is used).
 pub struct Connection<T> {
     pub is_initialized: bool,
     pub stream: Option<T>,
     pub timeout: Option<u64>,
-    pub proxy_addr: Option<(String, u16)>,
+    pub proxy: Option<Proxy>,
     pub addr: (String, u16),
+    pub protocol_version: i32,
+    pub intent: Intent,
+    pub codec: MinecraftCodec,
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<Encryption>,
+    #[cfg(feature = "resolve")]
+    pub nameserver: Option<SocketAddr>,
+    #[cfg(feature = "resolve")]
+    pub resolver: Arc<dyn Resolver>,
+    pub peer_addr: Option<SocketAddr>,
+    read_buf: BytesMut,
 }
 
 impl Connection<TcpStream> {
@@ -40,13 +257,13 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("play.example.com".to_string(), 25565)).await;
+    /// let conn = Connection::new(("play.example.com".to_string(), 25565));
     /// # Ok(())
     /// # }
     /// ```
@@ -55,8 +272,41 @@ impl Connection<TcpStream> {
             stream: None,
             timeout: None,
             is_initialized: true,
-            proxy_addr: None,
+            proxy: None,
             addr,
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+            intent: Intent::Status,
+            codec: MinecraftCodec::new(),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "resolve")]
+            nameserver: None,
+            #[cfg(feature = "resolve")]
+            resolver: Arc::new(SystemResolver),
+            peer_addr: None,
+            read_buf: BytesMut::new(),
+        }
+    }
+
+    /// Returns a copy of this connection's settings holding `stream`.
+    fn __with_stream(&self, stream: TcpStream) -> Self {
+        Self {
+            peer_addr: stream.peer_addr().ok(),
+            stream: Some(stream),
+            is_initialized: true,
+            timeout: self.timeout,
+            proxy: self.proxy.clone(),
+            addr: self.addr.clone(),
+            protocol_version: self.protocol_version,
+            intent: self.intent,
+            codec: MinecraftCodec::new(),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "resolve")]
+            nameserver: self.nameserver,
+            #[cfg(feature = "resolve")]
+            resolver: self.resolver.clone(),
+            read_buf: BytesMut::new(),
         }
     }
 
@@ -66,8 +316,14 @@ impl Connection<TcpStream> {
     /// established through that proxy. Otherwise, it connects directly.
     ///
     /// DNS resolution depends on the "resolve" feature flag:
-    /// - Without "resolve" feature: domain names are not supported (must be IP).
-    /// - With "resolve" feature enabled: domain names are resolved asynchronously.
+    /// - Without "resolve" feature: domain names are not supported (must be IP),
+    ///   unless they are sent to a SOCKS4a or SOCKS5 proxy to resolve.
+    /// - With "resolve" feature enabled: domain names are resolved asynchronously,
+    ///   after looking up `_minecraft._tcp` SRV records when the port is 25565.
+    ///   The handshake still carries the original host and port. Direct
+    ///   connections race all resolved addresses, IPv6 and IPv4 interleaved
+    ///   (RFC 8305 Happy Eyeballs); `peer_addr` tells which one was used. Names
+    ///   are resolved through `resolver()`, the system resolver by default.
     ///
     /// # Errors
     ///
@@ -75,13 +331,13 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("example.com".to_string(), 25565)).await;
+    /// let mut conn = Connection::new(("example.com".to_string(), 25565));
     /// conn = conn.connect().await?;
     /// # Ok(())
     /// # }
@@ -92,79 +348,439 @@ impl Connection<TcpStream> {
         #[cfg(not(feature = "resolve"))]
         {
             let addr = self.addr.clone();
-            if is_domain(&addr.0) {
+            // SOCKS4a and SOCKS5 proxies resolve host names themselves
+            let proxy_resolves = matches!(self.proxy, Some(Proxy::Socks4a { .. } | Proxy::Socks5 { .. }));
+            if is_domain(&addr.0) && !proxy_resolves {
                 return Err(anyhow!(r#"Enable feature "resolve" to enable domain resolving"#));
             }
 
-            match &self.proxy_addr {
+            match &self.proxy {
                 None => {
                     // Direct TCP connection with timeout
                     let stream = timeout(Duration::from_millis(_timeout), TcpStream::connect(addr.clone())).await??;
-                    Ok(Self {
-                        stream: Some(stream),
-                        is_initialized: true,
-                        timeout: self.timeout.clone(),
-                        proxy_addr: self.proxy_addr.clone(),
-                        addr: self.addr.clone(),
-                    })
+                    Ok(self.__with_stream(stream))
                 }
-                Some(proxy_addr) => {
-                    // Connect via SOCKS5 proxy with timeout
-                    let stream = timeout(
-                        Duration::from_millis(_timeout),
-                        Socks5Stream::connect(
-                            (proxy_addr.0.as_str(), proxy_addr.1),
-                            (addr.0.as_str(), addr.1)
-                        )
-                    ).await??;
-                    Ok(Self {
-                        stream: Some(stream.into_inner()),
-                        is_initialized: true,
-                        timeout: self.timeout.clone(),
-                        proxy_addr: self.proxy_addr.clone(),
-                        addr: self.addr.clone(),
-                    })
+                Some(proxy) => {
+                    // Connect via SOCKS proxy with timeout
+                    let deadline = tokio::time::Instant::now() + Duration::from_millis(_timeout);
+                    let stream = connect_through(proxy, (addr.0.as_str(), addr.1), deadline).await?;
+                    Ok(self.__with_stream(stream))
                 }
             }
         }
 
         #[cfg(feature = "resolve")]
         {
-            match &self.proxy_addr {
-                Some(proxy_addr) => {
-                    let stream = timeout(
-                        Duration::from_millis(_timeout),
-                        Socks5Stream::connect(
-                            (proxy_addr.0.as_str(), proxy_addr.1),
-                            (self.addr.0.as_str(), self.addr.1),
-                        )
-                    ).await??;
-
-                    Ok(Self {
-                        stream: Some(stream.into_inner()),
-                        is_initialized: true,
-                        timeout: self.timeout.clone(),
-                        proxy_addr: self.proxy_addr.clone(),
-                        addr: self.addr.clone(),
-                    })
+            // One deadline for the SRV lookup and every connection attempt
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(_timeout);
+            let mut last_err = None;
+            for (host, port) in self.__resolve_srv(deadline).await {
+                match self.__connect_resolved(&host, port, deadline).await {
+                    Ok(stream) => return Ok(self.__with_stream(stream)),
+                    Err(e) => last_err = Some(e),
                 }
-                None => {
-                    let host_port = format!("{}:{}", self.addr.0, self.addr.1);
-                    let mut addrs = lookup_host(host_port).await?;
-                    if let Some(sock_addr) = addrs.next() {
-                        let stream = timeout(Duration::from_millis(_timeout), TcpStream::connect(sock_addr)).await??;
-                        Ok(Self {
-                            stream: Some(stream),
-                            is_initialized: true,
-                            timeout: self.timeout.clone(),
-                            proxy_addr: None,
-                            addr: self.addr.clone(),
-                        })
+            }
+            Err(last_err.unwrap_or_else(|| anyhow!("Could not resolve address: {}", self.addr.0)))
+        }
+    }
+
+    /// Internal helper returning the `(host, port)` pairs to connect to.
+    ///
+    /// Like the vanilla client, the `_minecraft._tcp` SRV records are looked
+    /// up through the resolver when the port is the default 25565 and the host
+    /// is not an IP address. If there are any, their targets are used in
+    /// priority/weight order; otherwise, or if the lookup fails, the address
+    /// itself is used.
+    ///
+    /// The lookup gets at most half of the time left before `deadline`, so a
+    /// dead nameserver still leaves time to connect to the address itself.
+    #[cfg(feature = "resolve")]
+    async fn __resolve_srv(&self, deadline: tokio::time::Instant) -> Vec<(String, u16)> {
+        if self.addr.1 != DEFAULT_PORT || self.addr.0.parse::<IpAddr>().is_ok() {
+            return vec![self.addr.clone()];
+        }
+
+        let budget = deadline.saturating_duration_since(tokio::time::Instant::now()) / 2;
+        let records = match timeout(budget, self.resolver.resolve_srv(&self.addr.0, self.nameserver)).await {
+            Ok(Ok(lookup)) => order_srv_records(lookup.records),
+            _ => Vec::new(),
+        };
+
+        if records.is_empty() {
+            return vec![self.addr.clone()];
+        }
+        records.into_iter().map(|record| (record.target, record.port)).collect()
+    }
+
+    /// Internal helper to open a TCP stream to `host:port`, directly or through the proxy.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if resolving, connecting or the proxy handshake fails or
+    /// does not finish before `deadline`.
+    #[cfg(feature = "resolve")]
+    async fn __connect_resolved(&self, host: &str, port: u16, deadline: tokio::time::Instant) -> Result<TcpStream> {
+        match &self.proxy {
+            // SOCKS4 carries only IPv4 addresses, so the name is resolved here
+            Some(proxy @ Proxy::Socks4 { .. }) => timeout_at(deadline, async {
+                let ip = self.__resolve_ips(host).await?
+                    .into_iter()
+                    .find(IpAddr::is_ipv4)
+                    .ok_or_else(|| anyhow!("{} has no IPv4 address to send over SOCKS4", host))?;
+                connect_through(proxy, (ip.to_string().as_str(), port), deadline).await
+            }).await?,
+            Some(proxy) => connect_through(proxy, (host, port), deadline).await,
+            None => timeout_at(deadline, async {
+                let addrs = self.__resolve_ips(host).await?
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, port))
+                    .collect();
+                let (stream, _) = connect_happy_eyeballs(interleave_families(addrs), CONNECTION_ATTEMPT_DELAY).await?;
+                Ok(stream)
+            }).await?,
+        }
+    }
+
+    /// Internal helper resolving `host` through the resolver, unless it is an IP address.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if resolving fails or yields no address.
+    #[cfg(feature = "resolve")]
+    async fn __resolve_ips(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let ips = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => self.resolver.resolve(host).await?.addrs,
+        };
+        if ips.is_empty() {
+            return Err(anyhow!("Could not resolve address: {}", host));
+        }
+        Ok(ips)
+    }
+
+    /// Sets the DNS server used for SRV lookups instead of the one from `/etc/resolv.conf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let conn = Connection::new(("example.com".to_string(), 25565));
+    /// let conn = conn.nameserver("1.1.1.1:53".parse()?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "resolve")]
+    pub fn nameserver(mut self, nameserver: SocketAddr) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((addr, port)).nameserver(SocketAddr)"));
+        }
+
+        self.nameserver = Some(nameserver);
+        Ok(self)
+    }
+
+    /// Sets the resolver used for host names, e.g. a shared
+    /// [`CachingResolver`](crate::resolver::CachingResolver).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use std::sync::Arc;
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::resolver::{CachingResolver, SystemResolver};
+    ///
+    /// let resolver = Arc::new(CachingResolver::new(SystemResolver));
+    /// let conn = Connection::new(("example.com".to_string(), 25565)).resolver(resolver)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "resolve")]
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((addr, port)).resolver(Arc<dyn Resolver>)"));
+        }
+
+        self.resolver = resolver;
+        Ok(self)
+    }
+
+    /// Sets the proxy to connect through: SOCKS4, SOCKS4a or SOCKS5.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization, or if the proxy's user
+    /// id or credentials are empty or longer than 255 bytes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::proxy::Proxy;
+    ///
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// let conn = conn.proxy(Proxy::Socks4 { addr: ("127.0.0.1".to_string(), 1080), user_id: None })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((ip, port)).proxy(Proxy)"));
+        }
+        proxy.validate()?;
+
+        self.proxy = Some(proxy);
+        Ok(self)
+    }
+
+    /// Sets the SOCKS5 proxy address to use for connections.
+    ///
+    /// Shorthand for [`proxy`](Self::proxy) with [`Proxy::Socks5`] without credentials.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// let conn = conn.proxy_socks5(("127.0.0.1".to_string(), 1080))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy_socks5(mut self, proxy_addr: (String, u16)) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((ip, port)).proxy_socks5((ip, port))"));
+        }
+
+        self.proxy = Some(Proxy::Socks5 { addr: proxy_addr, credentials: None });
+        Ok(self)
+    }
+
+    /// Sets a SOCKS5 proxy that requires username/password authentication (RFC 1929).
+    ///
+    /// Shorthand for [`proxy`](Self::proxy) with [`Proxy::Socks5`] and credentials.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization, or if the username or
+    /// password is empty or longer than 255 bytes.
+    ///
+    /// Connecting then fails with [`ProxyError::AuthenticationFailed`](crate::proxy::ProxyError)
+    /// if the proxy rejects the credentials, and with `ProxyError::Unreachable` if
+    /// the proxy cannot be reached.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::proxy::ProxyError;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565))
+    ///     .proxy_socks5_auth(("127.0.0.1".to_string(), 1080), "user", "secret")?;
+    /// match conn.connect().await {
+    ///     Ok(_) => println!("connected"),
+    ///     Err(e) => match e.downcast_ref::<ProxyError>() {
+    ///         Some(ProxyError::AuthenticationFailed) => println!("wrong proxy credentials"),
+    ///         Some(ProxyError::Unreachable(reason)) => println!("proxy is down: {}", reason),
+    ///         _ => return Err(e),
+    ///     },
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy_socks5_auth(self, proxy_addr: (String, u16), username: &str, password: &str) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((ip, port)).proxy_socks5_auth((ip, port), username, password)"));
+        }
+        self.proxy(Proxy::Socks5 {
+            addr: proxy_addr,
+            credentials: Some((username.to_string(), password.to_string())),
+        })
+    }
+
+    /// Pings a server of unknown era, falling back to legacy pings.
+    ///
+    /// The modern handshake/status exchange is tried first on the current
+    /// stream. Only if the server rejects it before sending a status frame
+    /// (it closes or resets the connection, or answers with a legacy `0xFF`
+    /// kick) is the connection re-established and the legacy 1.6, 1.4 and Beta
+    /// pings tried in that order. Any other error, e.g. a timeout or an invalid
+    /// status JSON, comes from a modern server and is returned as is.
+    ///
+    /// Returns the status together with the variant that answered. A server that
+    /// answers a newer legacy ping in the Beta format is reported as
+    /// [`LegacyPingVariant::Beta`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, the modern exchange fails
+    /// without being rejected, reconnecting fails, or no legacy variant
+    /// produced a valid response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// let (status, variant) = conn.probe().await?;
+    /// println!("{:?} answered: {:?}", variant, status);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn probe(&mut self) -> Result<(ServerStatus, ProtocolVariant)> {
+        if !self.__request_modern_status().await? {
+            let status = self.__read_status_packet().await?.parse_status()?;
+            return Ok((status, ProtocolVariant::Modern));
+        }
+
+        let mut last_err = anyhow!("Server rejected the modern ping");
+        for variant in [LegacyPingVariant::V1_6, LegacyPingVariant::V1_4, LegacyPingVariant::Beta] {
+            *self = match self.connect().await {
+                Ok(conn) => conn,
+                Err(e) => return Err(e.context("Server rejected the modern ping, reconnecting for a legacy ping failed")),
+            };
+
+            match self.__ping_legacy(variant).await {
+                Ok(response) => {
+                    let variant = if response.server_version == LEGACY_VERSION_NAME
+                        && response.protocol_version == UNKNOWN_PROTOCOL_VERSION {
+                        LegacyPingVariant::Beta
                     } else {
-                        Err(anyhow!("Could not resolve address: {}", self.addr.0))
-                    }
+                        variant
+                    };
+                    return Ok((response.to_status(), ProtocolVariant::Legacy(variant)));
+                }
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(last_err.context("Server rejected the modern ping and did not answer any legacy ping"))
+    }
+
+    /// Internal helper sending the modern handshake and status request, then
+    /// waiting for the first bytes of the answer without consuming them.
+    ///
+    /// Returns true if the server rejected the request before sending a
+    /// status frame: it closed or reset the connection, or sent a legacy kick
+    /// (`0xFF` followed by the high byte of a short length, which a canonical
+    /// VarInt length never has).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, or sending or waiting
+    /// fails in any other way, e.g. by timing out.
+    async fn __request_modern_status(&mut self) -> Result<bool> {
+        let sent = match self.send_handshake().await {
+            Ok(()) => self.__send_query_packet().await,
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => {}
+            Err(e) if is_rejection(&e) => return Ok(true),
+            Err(e) => return Err(e),
+        }
+
+        let _timeout = self.timeout.unwrap_or(9000);
+        let stream = match &self.stream {
+            Some(s) => s,
+            None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect()?")),
+        };
+
+        let peeked: std::io::Result<Option<([u8; 2], usize)>> = timeout(Duration::from_millis(_timeout), async {
+            let mut first = [0u8; 2];
+            loop {
+                match stream.peek(&mut first).await? {
+                    0 => return Ok(None),
+                    1 if first[0] == 0xFF => tokio::time::sleep(Duration::from_millis(5)).await,
+                    n => return Ok(Some((first, n))),
                 }
             }
+        }).await?;
+
+        match peeked {
+            Ok(None) => Ok(true),
+            Ok(Some((first, n))) => Ok(n == 2 && first == [0xFF, 0x00]),
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                if is_rejection(&e) { Ok(true) } else { Err(e) }
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
+    /// Wraps an already connected stream, e.g. a Unix socket to a local proxy,
+    /// an in-memory duplex stream or a tunnel.
+    ///
+    /// `addr` is only sent in the handshake; no connection is made, so the
+    /// proxy and resolver settings have no effect. [`probe`](Connection::probe)
+    /// needs to reconnect and is only available for `TcpStream`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use tokio::net::UnixStream;
+    ///
+    /// let stream = UnixStream::connect("/run/mc-proxy.sock").await?;
+    /// let mut conn = Connection::from_stream(stream, ("play.example.com".to_string(), 25565));
+    /// let status = conn.ping().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_stream(stream: T, addr: (String, u16)) -> Self {
+        Self {
+            stream: Some(stream),
+            timeout: None,
+            is_initialized: true,
+            proxy: None,
+            addr,
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+            intent: Intent::Status,
+            codec: MinecraftCodec::new(),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "resolve")]
+            nameserver: None,
+            #[cfg(feature = "resolve")]
+            resolver: Arc::new(SystemResolver),
+            peer_addr: None,
+            read_buf: BytesMut::new(),
         }
     }
 
@@ -176,14 +792,14 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565)).await;
-    /// conn.timeout(5000).await?;
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// let conn = conn.timeout(5000)?;
     /// # Ok(())
     /// # }
     /// ```
@@ -195,37 +811,74 @@ impl Connection<TcpStream> {
         self.timeout = Some(timeout);
         Ok(self)
     }
-    /// Sets the SOCKS5 proxy address to use for connections.
+
+    /// Sets the protocol version sent in the handshake.
+    ///
+    /// Use `-1` to send "unknown" like the vanilla client does for server list pings.
+    /// Defaults to 768.
     ///
     /// # Errors
     ///
-    /// Returns error if called before initialization.
+    /// Returns error if called before initialization or if the version is below -1.
     ///
     /// # Example
     ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// let conn = conn.protocol_version(-1)?;
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn protocol_version(mut self, protocol_version: i32) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: Connection::new((ip, port)).protocol_version(i32)"));
+        }
+
+        validate_protocol_version(protocol_version)?;
+        self.protocol_version = protocol_version;
+        Ok(self)
+    }
+
+    /// Sets the intent (next state) sent in the handshake.
+    ///
+    /// Defaults to [`Intent::Status`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
+    /// use mc_ping::packets::Intent;
     ///
-    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565)).await;
-    /// conn.proxy_socks5(("127.0.0.1".to_string(), 1080)).await?;
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// let conn = conn.intent(Intent::Login)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn proxy_socks5(mut self, proxy_addr: (String, u16)) -> Result<Self> {
+    pub fn intent(mut self, intent: Intent) -> Result<Self> {
         if !self.is_initialized {
-            return Err(anyhow!("using: Connection::new((ip, port)).proxy((ip, port))"));
+            return Err(anyhow!("using: Connection::new((ip, port)).intent(Intent)"));
         }
 
-        self.proxy_addr = Some(proxy_addr);
+        self.intent = intent;
         Ok(self)
     }
 
     /// Sends the Minecraft handshake packet to the server.
     ///
-    /// This prepares the connection for status query or login.
+    /// This prepares the connection for status query or login, using the
+    /// configured protocol version and intent.
     ///
     /// # Errors
     ///
@@ -233,32 +886,57 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565)).await;
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
     /// conn = conn.connect().await?;
     /// conn.send_handshake().await?;
     /// # Ok(())
     /// # }
     /// ```
     pub async fn send_handshake(&mut self) -> Result<()> {
+        self.__send_handshake(self.intent).await
+    }
+
+    /// Internal helper to send the handshake with an explicit intent.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected or writing fails.
+    async fn __send_handshake(&mut self, intent: Intent) -> Result<()> {
+        let ip = self.addr.0.clone();
+        let port = self.addr.1;
+        let handshake = ClientHandshake::with_options(ip, port, self.protocol_version, intent)?;
+        self.send_packet(&handshake).await
+    }
+
+    /// Internal helper to write raw bytes to the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, writing fails or times out.
+    async fn __write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        #[cfg(feature = "encryption")]
+        let encrypted = self.encryption.as_mut().map(|encryption| {
+            let mut buf = bytes.to_vec();
+            encryption.encrypt(&mut buf);
+            buf
+        });
+        #[cfg(feature = "encryption")]
+        let bytes = encrypted.as_deref().unwrap_or(bytes);
+
         let stream = match &mut self.stream {
             Some(s) => s,
             None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect() ?")),
         };
 
-        let ip = self.addr.0.clone();
-        let port = self.addr.1;
-        let handshake = ClientHandshake::new(ip, port);
-        let bytes = handshake.to_bytes();
-
         timeout(
             Duration::from_millis(self.timeout.unwrap_or(9000)),
-            stream.write_all(bytes.as_slice())
+            stream.write_all(bytes)
         ).await??;
 
         Ok(())
@@ -270,34 +948,229 @@ impl Connection<TcpStream> {
     ///
     /// Returns error if writing to stream fails or stream is not connected.
     async fn __send_query_packet(&mut self) -> Result<()> {
-        let query = StatusQuery::new();
-        let bytes = query.to_bytes();
+        self.send_packet(&StatusQuery::new()).await
+    }
+
+    /// Internal helper to send the ping request packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if writing to stream fails or stream is not connected.
+    async fn __send_ping_packet(&mut self, payload: i64) -> Result<()> {
+        self.send_packet(&PingRequest::new(payload)).await
+    }
+
+    /// Switches the framing to compressed mode, as requested by Set Compression.
+    ///
+    /// Packets of `threshold` bytes or more are zlib-compressed from now on, in
+    /// both directions. A negative threshold disables compression.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// conn.set_compression(256);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_compression(&mut self, threshold: i32) {
+        self.codec.set_compression(threshold);
+    }
+
+    /// Reads a single packet and returns its body (packet id followed by data).
+    ///
+    /// Decompresses the packet if compression was enabled with [`set_compression`](Self::set_compression).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reading from stream fails, times out, stream is not
+    /// connected, or the packet cannot be decompressed.
+    pub async fn read_packet(&mut self) -> Result<Vec<u8>> {
+        let _timeout = self.timeout.unwrap_or(9000);
 
         let stream = match &mut self.stream {
             Some(s) => s,
             None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect()?")),
         };
 
-        stream.write_all(bytes.as_slice()).await?;
+        let codec = &mut self.codec;
+        let read_buf = &mut self.read_buf;
+        #[cfg(feature = "encryption")]
+        let encryption = &mut self.encryption;
+
+        timeout(Duration::from_millis(_timeout), async {
+            loop {
+                if let Some(body) = codec.decode_body(read_buf)? {
+                    return Ok(body);
+                }
+                #[cfg(feature = "encryption")]
+                let filled = read_buf.len();
+                if stream.read_buf(read_buf).await? == 0 {
+                    return Err(anyhow!("Connection closed in the middle of a packet"));
+                }
+                #[cfg(feature = "encryption")]
+                if let Some(encryption) = encryption {
+                    encryption.decrypt(&mut read_buf[filled..]);
+                }
+            }
+        }).await?
+    }
+
+    /// Enables AES-128-CFB8 encryption of the stream in both directions.
+    ///
+    /// Every byte read or written afterwards goes through the cipher, so
+    /// [`read_packet`](Self::read_packet) and [`write_packet`](Self::write_packet)
+    /// keep working unchanged.
+    #[cfg(feature = "encryption")]
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        let mut encryption = Encryption::new(shared_secret);
+        // Bytes buffered but not decoded yet arrived after the switch
+        encryption.decrypt(&mut self.read_buf);
+        self.encryption = Some(encryption);
+    }
+
+    /// Answers an Encryption Request and enables encryption.
+    ///
+    /// The shared secret and verify token are RSA-encrypted with the server's
+    /// DER public key and sent in an Encryption Response; all following traffic
+    /// is encrypted. For servers that authenticate, join the session server with
+    /// [`server_hash`](crate::encryption::server_hash) before calling this.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the public key is invalid, or the stream is not connected
+    /// or writing fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::{Connection, LoginProbe};
+    /// use mc_ping::encryption::generate_shared_secret;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// if let LoginProbe::OnlineMode { public_key, verify_token, .. } = conn.login_probe("Notch").await? {
+    ///     let secret = generate_shared_secret();
+    ///     conn.send_encryption_response(&public_key, &verify_token, &secret).await?;
+    ///     let next = conn.read_packet().await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "encryption")]
+    pub async fn send_encryption_response(
+        &mut self,
+        public_key: &[u8],
+        verify_token: &[u8],
+        shared_secret: &[u8; 16],
+    ) -> Result<()> {
+        let response = EncryptionResponse::new(
+            encrypt_with_public_key(public_key, shared_secret)?,
+            encrypt_with_public_key(public_key, verify_token)?,
+            self.protocol_version,
+        );
+        self.send_packet(&response).await?;
+        self.enable_encryption(shared_secret);
         Ok(())
     }
 
-    /// Internal helper to read the status response packet.
+    /// Writes a single packet from its body (packet id followed by data).
+    ///
+    /// Compresses the packet if compression was enabled with [`set_compression`](Self::set_compression).
     ///
     /// # Errors
     ///
-    /// Returns error if reading from stream fails or stream is not connected.
-    async fn __read_status_packet(&mut self) -> Result<ServerQueryResponse> {
-        let mut buf = [0u8; 10_000];
+    /// Returns error if the stream is not connected, writing fails or times out.
+    pub async fn write_packet(&mut self, body: &[u8]) -> Result<()> {
+        let mut frame = BytesMut::new();
+        self.codec.encode_body(body, &mut frame)?;
+        self.__write_bytes(&frame).await
+    }
+
+    /// Encodes and writes a single [`Packet`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the packet cannot be encoded, the stream is not
+    /// connected, writing fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::packets::{PingRequest, PongResponse};
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// let status = conn.ping().await?;
+    /// conn.send_packet(&PingRequest::new(42)).await?;
+    /// let pong: PongResponse = conn.receive_packet().await?;
+    /// assert_eq!(pong.payload, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_packet<P: Packet>(&mut self, packet: &P) -> Result<()> {
+        self.write_packet(&packet.to_body()?).await
+    }
+
+    /// Reads a single packet and decodes it as `P`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reading fails, or the packet is not a well-formed `P`.
+    pub async fn receive_packet<P: Packet>(&mut self) -> Result<P> {
+        let body = self.read_packet().await?;
+        P::from_body(&body)
+    }
+
+    /// Internal helper to read a legacy `0xFF` kick packet, including its
+    /// packet id and length prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reading from stream fails, times out, or stream is not connected.
+    async fn __read_legacy_kick(&mut self) -> Result<Vec<u8>> {
+        let _timeout = self.timeout.unwrap_or(9000);
 
         let stream = match &mut self.stream {
             Some(s) => s,
             None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect()?")),
         };
 
-        let n = stream.read(&mut buf).await?;
-        let status_packet = ServerQueryResponse::from(&buf[..n]).await;
-        Ok(status_packet)
+        timeout(Duration::from_millis(_timeout), async {
+            let packet_id = stream.read_u8().await?;
+            if packet_id != 0xFF {
+                return Err(anyhow!("Expected legacy kick packet (0xFF), got {:#04x}", packet_id));
+            }
+            let chars = stream.read_u16().await?;
+
+            let mut packet = vec![0u8; 3 + chars as usize * 2];
+            packet[0] = packet_id;
+            packet[1..3].copy_from_slice(&chars.to_be_bytes());
+            stream.read_exact(&mut packet[3..]).await?;
+            Ok(packet)
+        }).await?
+    }
+
+    /// Internal helper to read the status response packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reading from stream fails or stream is not connected.
+    async fn __read_status_packet(&mut self) -> Result<ServerQueryResponse> {
+        self.receive_packet().await
     }
 
     /// Sends a status query and reads the server response.
@@ -310,13 +1183,13 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("localhost".to_string(), 25565)).await;
+    /// let mut conn = Connection::new(("localhost".to_string(), 25565));
     /// conn = conn.connect().await?;
     /// conn.send_handshake().await?;
     /// let status = conn.get_status().await?;
@@ -327,7 +1200,7 @@ impl Connection<TcpStream> {
     pub async fn get_status(&mut self) -> Result<ServerStatus> {
         self.__send_query_packet().await?;
         let _status = self.__read_status_packet().await?;
-        Ok(_status.parse_status()?)
+        _status.parse_status()
     }
 
     /// Performs a full ping: sends handshake, status query, and parses the response.
@@ -340,13 +1213,13 @@ impl Connection<TcpStream> {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # use anyhow::Result;
     /// # #[tokio::main]
     /// # async fn main() -> Result<()> {
     /// use mc_ping::connection::Connection;
     ///
-    /// let mut conn = Connection::new(("play.example.com".to_string(), 25565)).await;
+    /// let mut conn = Connection::new(("play.example.com".to_string(), 25565));
     /// conn = conn.connect().await?;
     /// let status = conn.ping().await?;
     /// println!("Server status: {:?}", status);
@@ -355,8 +1228,206 @@ impl Connection<TcpStream> {
     /// ```
     pub async fn ping(&mut self) -> Result<ServerStatus> {
         self.send_handshake().await?;
-        self.__send_query_packet().await?;
-        let status = self.__read_status_packet().await?;
-        status.parse_status()
+        self.get_status().await
+    }
+
+    /// Performs a full ping and measures the round-trip latency.
+    ///
+    /// After the status exchange a Ping Request is sent and the time until the
+    /// matching Pong Response arrives is measured, like the vanilla server list does.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if any step fails or if the server echoes a different payload.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let mut conn = Connection::new(("play.example.com".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// let (status, latency) = conn.ping_with_latency().await?;
+    /// println!("{:?} in {} ms", status.version, latency.as_millis());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ping_with_latency(&mut self) -> Result<(ServerStatus, Duration)> {
+        let status = self.ping().await?;
+
+        let payload = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let started = Instant::now();
+        self.__send_ping_packet(payload).await?;
+        let pong: PongResponse = self.receive_packet().await?;
+        let latency = started.elapsed();
+
+        if pong.payload != payload {
+            return Err(anyhow!("Pong payload mismatch: sent {}, received {}", payload, pong.payload));
+        }
+
+        Ok((status, latency))
+    }
+
+    /// Performs a legacy (1.6) server list ping.
+    ///
+    /// Pre-Netty servers (1.6 and old Bukkit builds) do not speak the modern
+    /// handshake. This sends `0xFE 0x01` with an `MC|PingHost` plugin message and
+    /// maps the `0xFF` kick reply into [`ServerStatus`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, or if sending, reading or
+    /// parsing fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// let status = conn.ping_legacy().await?;
+    /// println!("Server status: {:?}", status);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ping_legacy(&mut self) -> Result<ServerStatus> {
+        self.ping_legacy_variant(LegacyPingVariant::V1_6).await
+    }
+
+    /// Performs a legacy server list ping using the given variant.
+    ///
+    /// Use [`LegacyPingVariant::V1_4`] for 1.4 - 1.5 servers and
+    /// [`LegacyPingVariant::Beta`] for Beta 1.8 - 1.3 servers, which do not report
+    /// a version (the version name is then [`LEGACY_VERSION_NAME`](crate::packets::LEGACY_VERSION_NAME)).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, or if sending, reading or
+    /// parsing fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::packets::LegacyPingVariant;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// let status = conn.ping_legacy_variant(LegacyPingVariant::Beta).await?;
+    /// println!("Server status: {:?}", status);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ping_legacy_variant(&mut self, variant: LegacyPingVariant) -> Result<ServerStatus> {
+        Ok(self.__ping_legacy(variant).await?.to_status())
+    }
+
+    /// Internal helper to perform a legacy ping and return the raw response.
+    async fn __ping_legacy(&mut self, variant: LegacyPingVariant) -> Result<LegacyPingResponse> {
+        let ping = LegacyPing::with_variant(self.addr.0.clone(), self.addr.1, variant);
+        self.__write_bytes(&ping.to_bytes()).await?;
+
+        let kick = self.__read_legacy_kick().await?;
+        LegacyPingResponse::from(&kick)
+    }
+
+    /// Starts a login to find out how the server treats new players, without joining.
+    ///
+    /// Sends a handshake with the login intent (next_state = 2) and a Login Start
+    /// for `username`, then classifies the first reply:
+    ///
+    /// * Encryption Request: [`LoginProbe::OnlineMode`]
+    /// * Set Compression / Login Success: [`LoginProbe::OfflineMode`]
+    /// * Disconnect (whitelist, ban, version mismatch): [`LoginProbe::Disconnected`]
+    /// * Login Plugin Request (proxy or modded): [`LoginProbe::PluginRequest`]
+    ///
+    /// The configured protocol version is used, so servers rejecting it answer
+    /// with a disconnect. If the reply was Set Compression, compression is
+    /// enabled so the following packets can be read with [`read_packet`](Self::read_packet).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the stream is not connected, the username is invalid, or
+    /// sending, reading or parsing fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::{Connection, LoginProbe};
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.connect().await?;
+    /// match conn.login_probe("Notch").await? {
+    ///     LoginProbe::OfflineMode { .. } => println!("cracked"),
+    ///     other => println!("{:?}", other),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn login_probe(&mut self, username: &str) -> Result<LoginProbe> {
+        self.__login_probe(Intent::Login, username).await
+    }
+
+    /// Starts a login with the transfer intent (next_state = 3), like a client
+    /// that was sent to this server with a Transfer packet.
+    ///
+    /// Servers with `accepts-transfers=false` disconnect the client right away;
+    /// servers accepting transfers continue with the normal login, so the reply
+    /// is classified like in [`login_probe`](Self::login_probe). Use
+    /// [`LoginProbe::is_accepted`] to tell the two apart.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the protocol version is older than 1.20.5 (766), the
+    /// stream is not connected, the username is invalid, or sending, reading or
+    /// parsing fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::{Connection, LoginProbe};
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    /// conn = conn.protocol_version(769)?.connect().await?;
+    /// match conn.transfer_probe("Notch").await? {
+    ///     LoginProbe::Disconnected { reason } => println!("transfer rejected: {}", reason),
+    ///     other => println!("transfer accepted: {:?}", other),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transfer_probe(&mut self, username: &str) -> Result<LoginProbe> {
+        self.__login_probe(Intent::Transfer, username).await
+    }
+
+    /// Internal helper to send a handshake with `intent` and classify the login reply.
+    async fn __login_probe(&mut self, intent: Intent, username: &str) -> Result<LoginProbe> {
+        let login = LoginStart::new(username.to_string(), 0, self.protocol_version)?;
+
+        self.__send_handshake(intent).await?;
+        self.send_packet(&login).await?;
+
+        let body = self.read_packet().await?;
+        let response = LoginResponse::from(&body, self.protocol_version)?;
+        if let LoginResponse::SetCompression { threshold } = response {
+            self.set_compression(threshold);
+        }
+        Ok(response.into())
     }
 }
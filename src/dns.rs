@@ -0,0 +1,317 @@
+//! Minimal DNS client for `_minecraft._tcp` SRV records ("resolve" feature).
+//!
+//! The vanilla client looks up `_minecraft._tcp.<host>` before connecting, so
+//! servers can be published on another host or port than the one players
+//! type in. The system resolver behind `lookup_host` cannot query SRV records,
+//! so this module sends a single recursive query over UDP to the nameserver
+//! from `/etc/resolv.conf` (or one given explicitly).
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use rand_core::{OsRng, RngCore};
+use tokio::net::UdpSocket;
+use tokio::sync::OnceCell;
+use tokio::time::timeout;
+
+/// SRV service prefix used by Java Edition servers.
+pub const MINECRAFT_SRV_PREFIX: &str = "_minecraft._tcp";
+
+/// Port the vanilla client uses when none is given, the only case it looks up SRV records.
+pub const DEFAULT_PORT: u16 = 25565;
+
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// Largest UDP response accepted.
+const MAX_RESPONSE_LEN: usize = 4096;
+
+/// Most compression pointers followed while reading one name.
+const MAX_POINTER_JUMPS: usize = 32;
+
+/// A SRV record: where a service is actually hosted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    /// Lower values are tried first.
+    pub priority: u16,
+    /// Relative share among records of the same priority.
+    pub weight: u16,
+    /// Port the service listens on.
+    pub port: u16,
+    /// Host the service runs on, without the trailing dot.
+    pub target: String,
+}
+
+/// Returns the first nameserver of `/etc/resolv.conf`, read once without
+/// blocking and kept for the rest of the process.
+pub(crate) async fn cached_system_nameserver() -> Option<SocketAddr> {
+    static NAMESERVER: OnceCell<Option<SocketAddr>> = OnceCell::const_new();
+    *NAMESERVER.get_or_init(|| async {
+        let conf = tokio::fs::read_to_string("/etc/resolv.conf").await.ok()?;
+        parse_resolv_conf(&conf)
+    }).await
+}
+
+/// Returns the first `nameserver` entry of a resolv.conf file.
+pub(crate) fn parse_resolv_conf(conf: &str) -> Option<SocketAddr> {
+    conf.lines()
+        .filter_map(|line| line.strip_prefix("nameserver"))
+        .filter_map(|rest| {
+            // Link-local IPv6 nameservers may carry a zone id, e.g. fe80::1%eth0
+            let ip = rest.split_whitespace().next()?.split('%').next()?;
+            ip.parse::<IpAddr>().ok()
+        })
+        .map(|ip| SocketAddr::new(ip, 53))
+        .next()
+}
+
+/// Looks up the `_minecraft._tcp` SRV records of `host` and returns them in
+/// the order they should be tried.
+///
+/// An empty list means the host has no SRV records and should be resolved
+/// as-is.
+///
+/// # Errors
+///
+/// Returns error if the nameserver cannot be reached, does not answer within
+/// `timeout_ms`, or sends a malformed or failed response.
+///
+/// # Example
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// use mc_ping::dns::lookup_minecraft_srv;
+///
+/// for record in lookup_minecraft_srv("example.com", "1.1.1.1:53".parse()?, 5000).await? {
+///     println!("{}:{}", record.target, record.port);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn lookup_minecraft_srv(host: &str, nameserver: SocketAddr, timeout_ms: u64) -> Result<Vec<SrvRecord>> {
+    let name = format!("{}.{}", MINECRAFT_SRV_PREFIX, host.trim_end_matches('.'));
+    let records = lookup_srv(&name, nameserver, timeout_ms).await?;
+    Ok(order_srv_records(records))
+}
+
+/// Sends a SRV query for `name` and returns the records in the answer, unordered.
+///
+/// # Errors
+///
+/// Returns error if the query fails, times out, or the response is malformed.
+pub async fn lookup_srv(name: &str, nameserver: SocketAddr, timeout_ms: u64) -> Result<Vec<SrvRecord>> {
+    timeout(Duration::from_millis(timeout_ms), query_srv(name, nameserver))
+        .await
+        .context("DNS query timed out")?
+        .map(|(records, _)| records)
+}
+
+/// Sends a SRV query for `name` and returns the records in the answer,
+/// unordered, with the smallest TTL among them. Waits for the answer until
+/// dropped, so callers put a timeout around it.
+pub(crate) async fn query_srv(name: &str, nameserver: SocketAddr) -> Result<(Vec<SrvRecord>, Option<Duration>)> {
+    let mut id = [0u8; 2];
+    OsRng.try_fill_bytes(&mut id).context("Failed to generate a DNS query id")?;
+    let query = build_query(u16::from_be_bytes(id), name)?;
+
+    let bind_addr: SocketAddr = if nameserver.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(nameserver).await?;
+
+    socket.send(&query).await?;
+    let mut buf = [0u8; MAX_RESPONSE_LEN];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        // Late answers to earlier queries on a reused port, and forged ones,
+        // are dropped
+        if is_answer_to(&buf[..len], &query) {
+            let (records, ttl) = parse_response(&buf[..len])?;
+            return Ok((records, ttl.map(|ttl| Duration::from_secs(u64::from(ttl)))));
+        }
+    }
+}
+
+/// Returns true if `message` is a response to `query`: same id, the response
+/// flag set, and the same single question (names compare case-insensitively).
+pub(crate) fn is_answer_to(message: &[u8], query: &[u8]) -> bool {
+    let question = &query[12..];
+    message.len() >= 12 + question.len()
+        && message[..2] == query[..2]
+        && message[2] & 0x80 != 0
+        && message[4..6] == [0x00, 0x01]
+        && message[12..12 + question.len()].eq_ignore_ascii_case(question)
+}
+
+/// Orders records for connecting (see [`order_records`]) with a random draw.
+pub(crate) fn order_srv_records(records: Vec<SrvRecord>) -> Vec<SrvRecord> {
+    order_records(records, random_below)
+}
+
+/// Sorts records by priority, and within a priority by the weighted random
+/// selection of RFC 2782. `random(n)` must return a value below `n`.
+pub(crate) fn order_records(mut records: Vec<SrvRecord>, mut random: impl FnMut(u32) -> u32) -> Vec<SrvRecord> {
+    records.sort_by_key(|record| record.priority);
+
+    let mut ordered = Vec::with_capacity(records.len());
+    let mut rest = records.as_slice();
+    while let Some(first) = rest.first() {
+        let same_priority = rest.iter().take_while(|record| record.priority == first.priority).count();
+        let (group, tail) = rest.split_at(same_priority);
+        rest = tail;
+
+        let mut group = group.to_vec();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|record| u32::from(record.weight)).sum();
+            let index = if total == 0 {
+                0
+            } else {
+                // Pick the first record whose running weight sum reaches the draw
+                let draw = random(total) + 1;
+                let mut sum = 0;
+                group.iter()
+                    .position(|record| {
+                        sum += u32::from(record.weight);
+                        sum >= draw
+                    })
+                    .unwrap_or(0)
+            };
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+/// Returns a uniformly distributed value below `bound`, rejecting draws from
+/// the incomplete range at the top that would favor small values.
+fn random_below(bound: u32) -> u32 {
+    let zone = u32::MAX - u32::MAX % bound;
+    loop {
+        let value = OsRng.next_u32();
+        if value < zone {
+            return value % bound;
+        }
+    }
+}
+
+/// Builds a recursive SRV query.
+pub(crate) fn build_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // Flags: recursion desired; one question, no other records
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("Invalid DNS name {:?}", name));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Parses a DNS response and returns its SRV answers and their smallest TTL
+/// in seconds, `None` if there are none.
+pub(crate) fn parse_response(message: &[u8]) -> Result<(Vec<SrvRecord>, Option<u32>)> {
+    let header = message.get(..12).ok_or_else(|| anyhow!("DNS response is truncated"))?;
+    let read_u16 = |at: usize| u16::from_be_bytes([header[at], header[at + 1]]);
+    let flags = read_u16(2);
+    if flags & 0x8000 == 0 {
+        return Err(anyhow!("DNS message is not a response"));
+    }
+    if flags & 0x0200 != 0 {
+        return Err(anyhow!("DNS response is truncated"));
+    }
+    match flags & 0x000F {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok((Vec::new(), None)),
+        rcode => return Err(anyhow!("DNS query failed with rcode {}", rcode)),
+    }
+    let questions = read_u16(4);
+    let answers = read_u16(6);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+
+    let mut records = Vec::new();
+    let mut min_ttl: Option<u32> = None;
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let fixed = message.get(pos..pos + 10).ok_or_else(|| anyhow!("DNS answer is truncated"))?;
+        let record_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let ttl = u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+        let data_len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let data_start = pos + 10;
+        let data = message.get(data_start..data_start + data_len).ok_or_else(|| anyhow!("DNS answer is truncated"))?;
+        pos = data_start + data_len;
+
+        // CNAMEs and other records may come along, only SRV answers matter
+        if record_type != TYPE_SRV {
+            continue;
+        }
+        if data.len() < 7 {
+            return Err(anyhow!("SRV record is truncated"));
+        }
+        let target = read_name(message, data_start + 6)?;
+        // A target of "." means the service is decidedly not available
+        if target.is_empty() {
+            continue;
+        }
+        min_ttl = Some(min_ttl.map_or(ttl, |min| min.min(ttl)));
+        records.push(SrvRecord {
+            priority: u16::from_be_bytes([data[0], data[1]]),
+            weight: u16::from_be_bytes([data[2], data[3]]),
+            port: u16::from_be_bytes([data[4], data[5]]),
+            target,
+        });
+    }
+    Ok((records, min_ttl))
+}
+
+/// Returns the position after the name starting at `pos`.
+fn skip_name(message: &[u8], mut pos: usize) -> Result<usize> {
+    loop {
+        let len = *message.get(pos).ok_or_else(|| anyhow!("DNS name is truncated"))?;
+        match len {
+            0 => return Ok(pos + 1),
+            // A compression pointer ends the name
+            len if len & 0xC0 == 0xC0 => return Ok(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// Reads the name starting at `pos`, following compression pointers.
+fn read_name(message: &[u8], mut pos: usize) -> Result<String> {
+    let mut labels = Vec::new();
+    let mut jumps = 0;
+    loop {
+        let len = *message.get(pos).ok_or_else(|| anyhow!("DNS name is truncated"))?;
+        if len == 0 {
+            return Ok(labels.join("."));
+        }
+        if len & 0xC0 == 0xC0 {
+            jumps += 1;
+            if jumps > MAX_POINTER_JUMPS {
+                return Err(anyhow!("DNS name has a compression loop"));
+            }
+            let low = *message.get(pos + 1).ok_or_else(|| anyhow!("DNS name is truncated"))?;
+            pos = (usize::from(len & 0x3F) << 8) | usize::from(low);
+            continue;
+        }
+        let label = message.get(pos + 1..pos + 1 + len as usize).ok_or_else(|| anyhow!("DNS name is truncated"))?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len as usize;
+    }
+}
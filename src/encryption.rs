@@ -0,0 +1,140 @@
+//! Protocol encryption (enabled with the "encryption" feature).
+//!
+//! After an Encryption Request the client picks a random 16-byte shared
+//! secret, RSA-encrypts it (and the verify token) with the server's public key
+//! and sends both in an Encryption Response. From then on every byte in both
+//! directions is encrypted with AES-128 in CFB8 mode, using the shared secret
+//! as both key and IV.
+//!
+//! [`Connection::send_encryption_response`](crate::connection::Connection::send_encryption_response)
+//! does all of that; the functions here are the building blocks.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use aes::Aes128;
+use anyhow::{Context as _, Result};
+use cfb8::cipher::inout::InOutBuf;
+use cfb8::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use rand_core::{OsRng, RngCore};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// AES-128-CFB8 state for both directions of a connection.
+pub struct Encryption {
+    encryptor: cfb8::Encryptor<Aes128>,
+    decryptor: cfb8::Decryptor<Aes128>,
+}
+
+impl Encryption {
+    /// Creates the cipher state from the shared secret (used as key and IV).
+    pub fn new(shared_secret: &[u8; 16]) -> Self {
+        Self {
+            encryptor: cfb8::Encryptor::new(shared_secret.into(), shared_secret.into()),
+            decryptor: cfb8::Decryptor::new(shared_secret.into(), shared_secret.into()),
+        }
+    }
+
+    /// Encrypts outgoing bytes in place.
+    pub fn encrypt(&mut self, buf: &mut [u8]) {
+        let (blocks, _) = InOutBuf::from(buf).into_chunks();
+        self.encryptor.encrypt_blocks_inout_mut(blocks);
+    }
+
+    /// Decrypts incoming bytes in place.
+    pub fn decrypt(&mut self, buf: &mut [u8]) {
+        let (blocks, _) = InOutBuf::from(buf).into_chunks();
+        self.decryptor.decrypt_blocks_inout_mut(blocks);
+    }
+}
+
+/// Reader that decrypts everything read from `inner`.
+pub struct DecryptReader<'a, R> {
+    inner: &'a mut R,
+    encryption: &'a mut Encryption,
+}
+
+impl<'a, R> DecryptReader<'a, R> {
+    /// Wraps `inner`, decrypting with `encryption`.
+    pub fn new(inner: &'a mut R, encryption: &'a mut Encryption) -> Self {
+        Self { inner, encryption }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DecryptReader<'_, R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut *this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            this.encryption.decrypt(&mut buf.filled_mut()[filled..]);
+        }
+        result
+    }
+}
+
+/// Generates a random 16-byte shared secret.
+pub fn generate_shared_secret() -> [u8; 16] {
+    let mut secret = [0u8; 16];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// RSA-encrypts `data` (PKCS#1 v1.5) with the server's DER encoded public key
+/// from the Encryption Request.
+///
+/// # Errors
+///
+/// Returns error if the public key cannot be parsed or encryption fails.
+pub fn encrypt_with_public_key(public_key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let key = RsaPublicKey::from_public_key_der(public_key)
+        .context("Failed to parse server public key")?;
+    key.encrypt(&mut OsRng, Pkcs1v15Encrypt, data)
+        .context("Failed to encrypt with server public key")
+}
+
+/// Computes the server hash sent to the session server when joining.
+///
+/// This is Minecraft's non-standard SHA-1 hex digest: the hash is read as a
+/// signed two's complement number, printed in hex without leading zeros.
+///
+/// # Example
+///
+/// ```
+/// use mc_ping::encryption::server_hash;
+///
+/// assert_eq!(server_hash("Notch", &[], &[]), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+/// assert_eq!(server_hash("jeb_", &[], &[]), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+/// ```
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hash: [u8; 20] = Sha1::new()
+        .chain_update(server_id.as_bytes())
+        .chain_update(shared_secret)
+        .chain_update(public_key)
+        .finalize()
+        .into();
+
+    let negative = hash[0] & 0x80 != 0;
+    if negative {
+        // two's complement: invert and add one
+        let mut carry = true;
+        for byte in hash.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (value, overflow) = byte.overflowing_add(1);
+                *byte = value;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    let hex = hex.trim_start_matches('0');
+    if negative {
+        format!("-{}", hex)
+    } else {
+        hex.to_string()
+    }
+}
@@ -0,0 +1,103 @@
+//! Packet framing for the post-handshake states.
+//!
+//! Without compression a frame is `length (VarInt) + body`, where the body is
+//! the packet id followed by the packet data. After the server sends Set
+//! Compression, every frame becomes
+//! `length (VarInt) + data length (VarInt) + data`: packets at or above the
+//! threshold are zlib-compressed and `data length` holds their uncompressed
+//! size, smaller packets are sent as-is with `data length` = 0.
+//!
+//! The functions here work on the frame contents after the outer length
+//! prefix, which is read by
+//! [`Connection::read_packet`](crate::connection::Connection::read_packet) and
+//! [`MinecraftCodec`](crate::codec::MinecraftCodec).
+
+use std::io::{Read, Write};
+use anyhow::{anyhow, Context, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use crate::varint::{VarInt, VarIntRead, VarIntWrite};
+
+/// Largest uncompressed packet accepted in compressed mode (8 MiB, as in vanilla).
+pub const MAX_UNCOMPRESSED_LEN: usize = 8_388_608;
+
+/// Encodes a packet body into a frame, including the outer length prefix.
+///
+/// `threshold` is `None` for uncompressed framing, or the compression
+/// threshold received in Set Compression.
+///
+/// # Errors
+///
+/// Returns error if zlib compression fails.
+///
+/// # Example
+///
+/// ```
+/// use mc_ping::framing::{decode_frame, encode_frame};
+///
+/// let body = vec![0x00; 300];
+/// let frame = encode_frame(&body, Some(256))?;
+/// // 300 zero bytes compress well, the length prefix is a single byte
+/// assert_eq!(decode_frame(&frame[1..], Some(256))?, body);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn encode_frame(body: &[u8], threshold: Option<usize>) -> Result<Vec<u8>> {
+    let mut contents = Vec::with_capacity(body.len() + 5);
+
+    match threshold {
+        None => contents.extend_from_slice(body),
+        Some(threshold) if body.len() < threshold => {
+            contents.write_var_int(VarInt::from(0))?;
+            contents.extend_from_slice(body);
+        }
+        Some(_) => {
+            contents.write_var_int(VarInt::from(body.len() as i32))?;
+            let mut encoder = ZlibEncoder::new(contents, Compression::default());
+            encoder.write_all(body)?;
+            contents = encoder.finish()?;
+        }
+    }
+
+    let mut frame = Vec::with_capacity(contents.len() + 5);
+    frame.write_var_int(VarInt::from(contents.len() as i32))?;
+    frame.extend(contents);
+    Ok(frame)
+}
+
+/// Decodes the contents of a frame (everything after the outer length prefix)
+/// into a packet body.
+///
+/// # Errors
+///
+/// Returns error if the data length is invalid, below the threshold for a
+/// compressed packet, above [`MAX_UNCOMPRESSED_LEN`], or does not match the
+/// decompressed size.
+pub fn decode_frame(contents: &[u8], threshold: Option<usize>) -> Result<Vec<u8>> {
+    let threshold = match threshold {
+        None => return Ok(contents.to_vec()),
+        Some(threshold) => threshold,
+    };
+
+    let mut cursor = contents;
+    let data_len = i32::from(cursor.read_var_int().context("Failed to read data length")?);
+    if data_len == 0 {
+        return Ok(cursor.to_vec());
+    }
+    if data_len < 0 || data_len as usize > MAX_UNCOMPRESSED_LEN {
+        return Err(anyhow!("Invalid uncompressed packet length: {}", data_len));
+    }
+    if (data_len as usize) < threshold {
+        return Err(anyhow!("Compressed packet of {} bytes is below the threshold {}", data_len, threshold));
+    }
+
+    let mut body = Vec::with_capacity(data_len as usize);
+    ZlibDecoder::new(cursor)
+        .take(data_len as u64 + 1)
+        .read_to_end(&mut body)
+        .context("Failed to decompress packet")?;
+    if body.len() != data_len as usize {
+        return Err(anyhow!("Decompressed packet is {} bytes, expected {}", body.len(), data_len));
+    }
+    Ok(body)
+}
@@ -1,18 +1,1337 @@
 #![crate_type = "rlib"]
-
-#[doc = include_str!("../README.md")]
+#![doc = include_str!("../README.md")]
 
 pub mod connection;
 pub mod packets;
-mod varint;
+pub mod framing;
+pub mod codec;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod varint;
+pub mod types;
+pub mod nbt;
 pub mod mc_text;
+pub mod bedrock;
+pub mod query;
+pub mod rcon;
+pub mod proxy;
+#[cfg(feature = "resolve")]
+pub mod dns;
+#[cfg(feature = "resolve")]
+pub mod resolver;
+
+/// Reads one length-prefixed frame from `reader` and returns its contents,
+/// i.e. what a stand-in server receives from [`connection::Connection`].
+#[cfg(test)]
+async fn read_frame<R: tokio::io::AsyncRead + Unpin + Send>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+    use crate::varint::AsyncVarIntRead;
+
+    let len = i32::from(reader.read_var_int_async().await?);
+    if len <= 0 || len as usize > connection::MAX_PACKET_LEN {
+        return Err(anyhow::anyhow!("Invalid packet length: {}", len));
+    }
+
+    let mut body = vec![0u8; len as usize];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// Spawns a stand-in status server on an ephemeral localhost port that answers
+/// one connection with [`serve_status`].
+#[cfg(test)]
+async fn spawn_status_server(json: String, chunk: usize) -> anyhow::Result<u16> {
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        serve_status(socket, json, chunk).await;
+    });
+    Ok(port)
+}
+
+/// Answers one handshake + status request on `socket` with `json`, written in
+/// `chunk`-sized pieces, then echoes a following ping request back as the pong.
+#[cfg(test)]
+async fn serve_status<S>(mut socket: S, json: String, chunk: usize)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send,
+{
+    use tokio::io::AsyncWriteExt;
+
+    // handshake, then status request
+    read_frame(&mut socket).await.unwrap();
+    read_frame(&mut socket).await.unwrap();
+
+    let mut body = Vec::new();
+    varint::VarIntWrite::write_var_int(&mut body, varint::VarInt::from(0x00)).unwrap();
+    varint::VarIntWrite::write_var_int(&mut body, varint::VarInt::from(json.len() as i32)).unwrap();
+    body.extend_from_slice(json.as_bytes());
+    let mut frame = Vec::new();
+    varint::VarIntWrite::write_var_int(&mut frame, varint::VarInt::from(body.len() as i32)).unwrap();
+    frame.extend_from_slice(&body);
+
+    for piece in frame.chunks(chunk) {
+        socket.write_all(piece).await.unwrap();
+        socket.flush().await.unwrap();
+        tokio::task::yield_now().await;
+    }
+
+    if let Ok(ping) = read_frame(&mut socket).await {
+        socket.write_all(&[ping.len() as u8]).await.unwrap();
+        socket.write_all(&ping).await.unwrap();
+    }
+}
 
 #[tokio::test]
 async fn test_localhost() -> anyhow::Result<()> {
     use crate::connection::Connection;
-    let mut conn = Connection::new(("127.0.0.1".to_string(), 25565));
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":3},"description":"A Minecraft Server"}"#;
+    let port = spawn_status_server(json.to_string(), usize::MAX).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
     conn = conn.timeout(7000)?.connect().await?;
     let status = conn.ping().await.unwrap();
     println!("{:?}", status);
+    assert_eq!(status.players.online, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connection_over_any_stream() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":3},"description":"A Minecraft Server"}"#;
+
+    let (client, server) = tokio::io::duplex(64);
+    tokio::spawn(serve_status(server, json.to_string(), 7));
+    let mut conn = Connection::from_stream(client, ("play.example.com".to_string(), 25565)).timeout(7000)?;
+    let (status, _) = conn.ping_with_latency().await?;
+    assert_eq!(status.players.online, 3);
+    assert_eq!(conn.peer_addr, None);
+
+    #[cfg(unix)]
+    {
+        let (client, server) = tokio::net::UnixStream::pair()?;
+        tokio::spawn(serve_status(server, json.to_string(), usize::MAX));
+        let mut conn = Connection::from_stream(client, ("localhost".to_string(), 25565));
+        assert_eq!(conn.ping().await?.players.max, 20);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fragmented_large_status() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    let favicon = "A".repeat(150_000);
+    let json = format!(
+        r#"{{"version":{{"name":"1.20.1","protocol":763}},"players":{{"max":100,"online":7}},"description":"modded","favicon":"data:image/png;base64,{}"}}"#,
+        favicon
+    );
+    let port = spawn_status_server(json, 1_000).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let status = conn.ping().await?;
+    assert_eq!(status.favicon.map(|f| f.len()), Some(150_000 + 22));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ping_latency() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":0},"description":""}"#;
+    let port = spawn_status_server(json.to_string(), usize::MAX).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let (status, latency) = conn.ping_with_latency().await?;
+    assert_eq!(status.version.protocol, 769);
+    assert!(latency < std::time::Duration::from_secs(7));
+    Ok(())
+}
+
+#[test]
+fn test_handshake_options() -> anyhow::Result<()> {
+    use crate::packets::{ClientHandshake, Intent};
+    let handshake = ClientHandshake::with_options("mc".to_string(), 25565, -1, Intent::Login)?;
+    assert_eq!(
+        handshake.to_bytes()?,
+        vec![0x0C, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x02, b'm', b'c', 0x63, 0xDD, 0x02]
+    );
+    assert!(ClientHandshake::with_options("mc".to_string(), 25565, -2, Intent::Status).is_err());
+    // `new` does not check the address, encoding does
+    assert!(ClientHandshake::new("a".repeat(256), 25565).to_bytes().is_err());
+    assert!(ClientHandshake::with_options("a".repeat(256), 25565, -1, Intent::Status).is_err());
+    assert!(Intent::try_from(4).is_err());
+    assert!(crate::connection::Connection::new(("mc".to_string(), 25565)).protocol_version(-5).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_status_packets_round_trip() -> anyhow::Result<()> {
+    use crate::packets::{ClientHandshake, Intent, Packet, PingRequest, PongResponse, ServerQueryResponse, StatusQuery};
+
+    let handshake = ClientHandshake::with_options("mc".to_string(), 25565, 769, Intent::Transfer)?;
+    let decoded = ClientHandshake::from_body(&handshake.to_body()?)?;
+    assert_eq!(decoded.to_bytes()?, handshake.to_bytes()?);
+    assert_eq!(decoded.server_addr, "mc");
+    assert_eq!(decoded.server_port, 25565);
+
+    assert_eq!(StatusQuery::new().to_bytes()?, vec![0x01, 0x00]);
+    StatusQuery::from_body(&[0x00])?;
+    assert!(StatusQuery::from_body(&[0x00, 0x00]).is_err());
+
+    let json = r#"{"description":"§aé"}"#;
+    let response = ServerQueryResponse::from_body(&ServerQueryResponse::new(json.to_string()).to_body()?)?;
+    assert_eq!(response.json, json);
+    assert_eq!(i32::from(response.len), 2 + json.len() as i32);
+
+    assert_eq!(PingRequest::from_body(&PingRequest::new(-7).to_body()?)?.payload, -7);
+    assert_eq!(PongResponse::from_body(&PongResponse::new(i64::MAX).to_body()?)?.payload, i64::MAX);
+    // A ping is not a status response, and a pong must carry all 8 payload bytes
+    assert!(ServerQueryResponse::from_body(&PingRequest::new(1).to_body()?).is_err());
+    assert!(PongResponse::from_body(&[0x01, 0x00, 0x00]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_login_packets_round_trip() -> anyhow::Result<()> {
+    use crate::packets::{EncryptionRequest, EncryptionResponse, LoginResponse, LoginStart, LoginSuccess, Packet, ProfileProperty};
+
+    let login = LoginStart::new("Notch".to_string(), 7, 764)?;
+    let decoded = LoginStart::from_body(&login.to_body()?)?;
+    assert_eq!((decoded.username.as_str(), decoded.uuid), ("Notch", 7));
+    // 1.19.1: no signature data, then an optional uuid
+    let body = LoginStart::new("Notch".to_string(), 7, 760)?.to_body()?;
+    assert_eq!(&body[7..9], &[0x00, 0x01]);
+    assert_eq!(body.len(), 9 + 16);
+    assert_eq!(LoginStart::new("Notch".to_string(), 7, 758)?.to_body()?.len(), 7);
+    // Fields set by hand are still checked when encoding
+    let login = LoginStart { username: "a".repeat(17), uuid: 0, protocol_version: 764 };
+    assert!(login.to_body().is_err());
+
+    let response = EncryptionResponse::new(vec![1, 2], vec![3], 760);
+    assert_eq!(response.to_body()?, vec![0x01, 0x02, 1, 2, 0x01, 0x01, 3]);
+    let decoded = EncryptionResponse::from_body(&EncryptionResponse::new(vec![1, 2], vec![3], 769).to_body()?)?;
+    assert_eq!((decoded.shared_secret, decoded.verify_token), (vec![1, 2], vec![3]));
+
+    let request = EncryptionRequest {
+        server_id: String::new(),
+        public_key: vec![9; 4],
+        verify_token: vec![1, 2, 3, 4],
+        should_authenticate: false,
+    };
+    assert_eq!(EncryptionRequest::from_body(&request.to_body()?)?, request);
+    // Before 1.20.5 the flag is missing and authentication is required
+    let old = &request.to_body()?[..12];
+    assert!(EncryptionRequest::from_body(old)?.should_authenticate);
+
+    let success = LoginSuccess {
+        uuid: 1,
+        username: "Notch".to_string(),
+        properties: vec![ProfileProperty { name: "textures".to_string(), value: "e30=".to_string(), signature: None }],
+    };
+    let mut body = success.to_body()?;
+    assert_eq!(LoginSuccess::from_body(&body)?, success);
+    // 1.20.5 - 1.21.1 add a "strict error handling" flag
+    body.push(0x01);
+    assert_eq!(LoginResponse::from(&body, 766)?, LoginResponse::LoginSuccess { uuid: 1, username: "Notch".to_string() });
+    body.push(0x01);
+    assert!(LoginResponse::from(&body, 766).is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_varint() -> anyhow::Result<()> {
+    use crate::varint::{AsyncVarIntRead, AsyncVarLongRead, AsyncVarLongWrite, VarInt, VarLong};
+
+    let mut buf = Vec::new();
+    buf.write_var_long_async(VarLong::from(-1)).await?;
+    assert_eq!(buf.len(), 10);
+    assert_eq!(i64::from((&buf[..]).read_var_long_async().await?), -1);
+
+    // Six continuation bytes can never be a valid VarInt
+    assert!((&[0x80u8; 6][..]).read_var_int_async().await.is_err());
+    assert_eq!((&[0x00u8][..]).read_var_int_async().await?, VarInt::from(0));
+    Ok(())
+}
+
+#[test]
+fn test_varint_codec() -> anyhow::Result<()> {
+    use std::io::ErrorKind;
+    use crate::varint::{VarInt, VarIntRead, VarIntWrite, VarLong};
+
+    for n in [0, 1, 127, 128, 255, 25565, 2_097_151, 2_097_152, i32::MAX, -1, i32::MIN] {
+        let var = VarInt::from(n);
+        assert_eq!(var.size(), VarInt::encoded_len(n));
+        assert_eq!(VarInt::decode_slice(var.as_bytes())?, (var.clone(), var.size()));
+        assert_eq!(i32::from(var), n);
+    }
+    assert_eq!(VarInt::from(i32::MIN).as_bytes(), &[0x80, 0x80, 0x80, 0x80, 0x08]);
+    for n in [0, 300, i64::MAX, -1, i64::MIN] {
+        let var = VarLong::from(n);
+        assert_eq!(var.size(), VarLong::encoded_len(n));
+        assert_eq!(i64::from(VarLong::decode_slice(var.as_bytes())?.0), n);
+    }
+    assert_eq!(VarLong::from(-1).size(), 10);
+
+    // Writers used to stop at the first zero byte of a non-canonical encoding
+    let (padded, _) = VarInt::decode_slice(&[0x80, 0x00])?;
+    let mut buf = Vec::new();
+    buf.write_var_int(padded)?;
+    assert_eq!(buf, vec![0x80, 0x00]);
+
+    assert_eq!(VarInt::decode_slice(&[0x80, 0x80]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(VarInt::decode_slice(&[0x80; 6]).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(VarInt::decode_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x10]).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(VarLong::decode_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02]).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert!((&[0xFFu8, 0xFF, 0xFF, 0xFF, 0x7F][..]).read_var_int().is_err());
+    assert_eq!(VarInt::from(25565).encode_slice(&mut [0u8; 2]).unwrap_err().kind(), ErrorKind::WriteZero);
+
+    // A hostile inner array must not panic the conversion
+    assert_eq!(i32::from(VarInt { inner: [0xFF; 5] }), -1);
+    Ok(())
+}
+
+#[test]
+fn test_types_codec() -> anyhow::Result<()> {
+    use crate::types::{Decode, Encode, Identifier, Position, RemainingBytes, MAX_STRING_LEN};
+
+    let mut buf = Vec::new();
+    "é".repeat(MAX_STRING_LEN).encode(&mut buf)?;
+    assert_eq!(String::decode(&mut &buf[..])?.chars().count(), MAX_STRING_LEN);
+    assert!("a".repeat(MAX_STRING_LEN + 1).encode(&mut Vec::new()).is_err());
+
+    let mut buf = Vec::new();
+    (u128::MAX - 1).encode(&mut buf)?;
+    Option::<i32>::None.encode(&mut buf)?;
+    Position::new(-33_554_432, -2048, 33_554_431).encode(&mut buf)?;
+    vec![true, false].encode(&mut buf)?;
+    RemainingBytes(vec![1, 2, 3]).encode(&mut buf)?;
+
+    let mut cursor = &buf[..];
+    assert_eq!(u128::decode(&mut cursor)?, u128::MAX - 1);
+    assert_eq!(Option::<i32>::decode(&mut cursor)?, None);
+    assert_eq!(Position::decode(&mut cursor)?, Position::new(-33_554_432, -2048, 33_554_431));
+    assert_eq!(Vec::<bool>::decode(&mut cursor)?, vec![true, false]);
+    assert_eq!(RemainingBytes::decode(&mut cursor)?.0, vec![1, 2, 3]);
+    assert!(cursor.is_empty());
+
+    assert!(Position::new(0, 2048, 0).encode(&mut Vec::new()).is_err());
+    assert!(bool::decode(&mut &[0x02][..]).is_err());
+    // A length prefix larger than the packet must fail before allocating
+    assert!(Vec::<u8>::decode(&mut &[0xFF, 0xFF, 0xFF, 0xFF, 0x07][..]).is_err());
+    assert!(String::decode(&mut &[0x02, 0xC3][..]).is_err());
+    assert!(Identifier::new("minecraft", "").is_err());
+    assert!("a:b:c".parse::<Identifier>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_nbt() -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+    use crate::mc_text::Description;
+    use crate::nbt::{read_named, read_network, write_named, write_network, Tag};
+
+    let compound = Tag::Compound(BTreeMap::from([
+        ("byte".to_string(), Tag::Byte(-1)),
+        ("short".to_string(), Tag::Short(300)),
+        ("int".to_string(), Tag::Int(i32::MIN)),
+        ("long".to_string(), Tag::Long(i64::MAX)),
+        ("float".to_string(), Tag::Float(0.5)),
+        ("double".to_string(), Tag::Double(-2.25)),
+        ("bytes".to_string(), Tag::ByteArray(vec![1, -2])),
+        // NUL and characters outside the BMP are where modified UTF-8 differs
+        ("string".to_string(), Tag::String("a\0b😀".to_string())),
+        ("list".to_string(), Tag::List(vec![Tag::Int(1), Tag::Int(2)])),
+        ("empty".to_string(), Tag::List(vec![])),
+        ("ints".to_string(), Tag::IntArray(vec![7, -7])),
+        ("longs".to_string(), Tag::LongArray(vec![i64::MIN])),
+    ]));
+
+    let mut named = Vec::new();
+    write_named("root", &compound, &mut named)?;
+    assert_eq!(&named[..7], &[0x0A, 0x00, 0x04, b'r', b'o', b'o', b't']);
+    let mut cursor = &named[..];
+    assert_eq!(read_named(&mut cursor)?, ("root".to_string(), compound.clone()));
+    assert!(cursor.is_empty());
+
+    let mut network = Vec::new();
+    write_network(&compound, &mut network)?;
+    assert_eq!(network.len(), named.len() - 6);
+    assert_eq!(read_network(&mut &network[..])?, Some(compound.clone()));
+    assert!(network.windows(2).any(|pair| pair == [0xC0, 0x80]));
+    assert_eq!(read_network(&mut &[0x00][..])?, None);
+
+    // A 1.20.3+ disconnect reason with a list of mixed types
+    let json = serde_json::json!({
+        "text": "Kicked",
+        "bold": true,
+        "color": "red",
+        "extra": ["!", {"text": "?", "italic": false, "obfuscated": true}],
+    });
+    let reason = Tag::try_from(json.clone())?;
+    let mut buf = Vec::new();
+    write_network(&reason, &mut buf)?;
+    let decoded = read_network(&mut &buf[..])?.unwrap();
+    assert_eq!(decoded.get("bold"), Some(&Tag::Byte(1)));
+    match Description::from_nbt(decoded.clone())? {
+        Description::Complex(value) => assert_eq!(value, json),
+        other => panic!("expected a complex description, got {:?}", other),
+    }
+
+    // Typed targets read bytes as booleans
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Style {
+        bold: bool,
+        italic: Option<bool>,
+        color: Option<String>,
+    }
+    let style: Style = crate::nbt::from_tag(decoded)?;
+    assert_eq!(style, Style { bold: true, italic: None, color: Some("red".to_string()) });
+    let ints: Vec<i32> = crate::nbt::from_tag(Tag::IntArray(vec![7, -7]))?;
+    assert_eq!(ints, vec![7, -7]);
+    assert!(crate::nbt::from_tag::<Style>(Tag::String("red".to_string())).is_err());
+
+    assert!(write_network(&Tag::List(vec![Tag::Int(1), Tag::Byte(1)]), &mut Vec::new()).is_err());
+    // Lists nested 600 deep, closed by an empty list of TAG_End
+    let deep = [&[0x09][..], &[0x09, 0x00, 0x00, 0x00, 0x01].repeat(600), &[0x00, 0x00, 0x00, 0x00, 0x00]].concat();
+    let err = read_network(&mut &deep[..]).unwrap_err();
+    assert!(err.to_string().contains("nested deeper"));
+    assert!(read_network(&mut &[0x07, 0x7F, 0xFF, 0xFF, 0xFF][..]).is_err());
+    assert!(read_network(&mut &[0x0A, 0x01, 0x00, 0x01, b'a', 0x05][..]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_minecraft_codec() -> anyhow::Result<()> {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+    use crate::codec::MinecraftCodec;
+
+    let mut codec = MinecraftCodec::new();
+    codec.set_compression(64);
+    let mut buf = BytesMut::new();
+    codec.encode((0x26, vec![7u8; 1000]), &mut buf)?;
+    codec.encode((0x00, b"hi".as_slice()), &mut buf)?;
+    assert!(buf.len() < 100);
+
+    // Frames arriving one byte at a time are only yielded once complete
+    let mut partial = BytesMut::new();
+    let mut items = Vec::new();
+    for byte in buf.iter() {
+        partial.extend_from_slice(&[*byte]);
+        if let Some(item) = codec.decode(&mut partial)? {
+            items.push(item);
+        }
+    }
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].0, 0x26);
+    assert_eq!(items[0].1[..], [7u8; 1000]);
+    assert_eq!(items[1], (0x00, bytes::Bytes::from_static(b"hi")));
+    assert!(partial.is_empty());
+
+    let mut small = MinecraftCodec::new().max_frame_len(16);
+    assert!(small.encode((0x00, [0u8; 16]), &mut BytesMut::new()).is_err());
+    assert!(small.decode(&mut BytesMut::from(&[0x11u8][..])).is_err());
+    assert!(small.decode(&mut BytesMut::from(&[0xFFu8; 6][..])).is_err());
+    Ok(())
+}
+
+/// Spawns a stand-in pre-Netty server that reads `request_len` bytes and
+/// answers with a `0xFF` kick packet carrying `reply`.
+#[cfg(test)]
+async fn spawn_legacy_server(request_len: usize, reply: &'static str) -> anyhow::Result<u16> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = vec![0u8; request_len];
+        socket.read_exact(&mut request).await.unwrap();
+
+        let units: Vec<u16> = reply.encode_utf16().collect();
+        let mut kick = vec![0xFF];
+        kick.extend_from_slice(&(units.len() as u16).to_be_bytes());
+        for unit in units {
+            kick.extend_from_slice(&unit.to_be_bytes());
+        }
+        socket.write_all(&kick).await.unwrap();
+    });
+    Ok(port)
+}
+
+#[tokio::test]
+async fn test_legacy_ping() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    use crate::mc_text::Description;
+    let request_len = packets::LegacyPing::new("127.0.0.1".to_string(), 0).to_bytes().len();
+    let port = spawn_legacy_server(request_len, "§1\x0074\x001.6.4\x00A Legacy Server\x005\x0020").await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let status = conn.ping_legacy().await?;
+    assert_eq!(status.version.name, "1.6.4");
+    assert_eq!(status.version.protocol, 74);
+    assert_eq!((status.players.online, status.players.max), (5, 20));
+    assert!(matches!(status.description, Description::Text(ref motd) if motd == "A Legacy Server"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_legacy_ping_variants() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    use crate::packets::{LegacyPingVariant, LEGACY_VERSION_NAME};
+
+    let port = spawn_legacy_server(1, "§cOld §fServer§3§10").await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let status = conn.ping_legacy_variant(LegacyPingVariant::Beta).await?;
+    assert_eq!(status.version.name, LEGACY_VERSION_NAME);
+    assert_eq!((status.players.online, status.players.max), (3, 10));
+    assert!(matches!(status.description, mc_text::Description::Text(ref motd) if motd == "§cOld §fServer"));
+
+    let port = spawn_legacy_server(2, "§1\x0049\x001.4.7\x00Tekkit\x000\x0016").await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let status = conn.ping_legacy_variant(LegacyPingVariant::V1_4).await?;
+    assert_eq!((status.version.name.as_str(), status.version.protocol), ("1.4.7", 49));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_probe_falls_back_to_legacy() -> anyhow::Result<()> {
+    use crate::connection::{Connection, ProtocolVariant};
+    use crate::packets::LegacyPingVariant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn kick(reply: &str) -> Vec<u8> {
+        let units: Vec<u16> = reply.encode_utf16().collect();
+        let mut kick = vec![0xFF];
+        kick.extend_from_slice(&(units.len() as u16).to_be_bytes());
+        units.iter().for_each(|unit| kick.extend_from_slice(&unit.to_be_bytes()));
+        kick
+    }
+
+    // Behaves like a 1.6 server: the modern handshake is an unknown packet id.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let packet_id = socket.read_u8().await.unwrap();
+            let reply = if packet_id == 0xFE {
+                kick("§1\x0078\x001.6.4\x00Old Network\x0012\x00100")
+            } else {
+                kick(&format!("Bad packet id {}", packet_id))
+            };
+            socket.write_all(&reply).await.unwrap();
+        }
+    });
+
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port));
+    conn = conn.timeout(7000)?.connect().await?;
+    let (status, variant) = conn.probe().await?;
+    assert_eq!(variant, ProtocolVariant::Legacy(LegacyPingVariant::V1_6));
+    assert_eq!(status.players.online, 12);
+
+    // A modern server with a broken status is not mistaken for a legacy one
+    let port = spawn_status_server("not json".to_string(), usize::MAX).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let err = conn.probe().await.unwrap_err();
+    assert!(err.to_string().contains("Failed to deserialize"), "{:#}", err);
+
+    // Neither is a silent one, which fails after one timeout
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(300)?.connect().await?;
+    let started = std::time::Instant::now();
+    assert!(conn.probe().await.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+    drop(listener);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bedrock_ping() -> anyhow::Result<()> {
+    use crate::bedrock::BedrockConnection;
+    use crate::packets::{UnconnectedPing, RAKNET_MAGIC};
+    use tokio::net::UdpSocket;
+
+    let server = UdpSocket::bind("127.0.0.1:0").await?;
+    let port = server.local_addr()?.port();
+    tokio::spawn(async move {
+        loop {
+            let mut buf = [0u8; 64];
+            let (n, peer) = server.recv_from(&mut buf).await.unwrap();
+            assert_eq!(n, UnconnectedPing::new(0, 0).to_bytes().len());
+
+            let data = b"MCPE;Geyser Proxy;766;1.21.50;4;50;123;Lobby;Survival;1;19132;19133;";
+            let mut pong = vec![0x1C];
+            pong.extend_from_slice(&buf[1..9]);
+            pong.extend_from_slice(&42i64.to_be_bytes());
+            pong.extend_from_slice(&RAKNET_MAGIC);
+            pong.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            pong.extend_from_slice(data);
+            server.send_to(&pong, peer).await.unwrap();
+        }
+    });
+
+    let status = BedrockConnection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.ping().await?;
+    assert_eq!(status.motd, "Geyser Proxy");
+    assert_eq!((status.online, status.max), (4, 50));
+    assert_eq!(status.sub_motd.as_deref(), Some("Lobby"));
+
+    // Host names go through the configured resolver
+    #[cfg(feature = "resolve")]
+    {
+        use std::sync::Arc;
+        use crate::resolver::{StaticResolver, SystemResolver};
+
+        let resolver = Arc::new(StaticResolver::new(SystemResolver).host("bedrock.staging.test", "127.0.0.1".parse()?));
+        let status = BedrockConnection::new(("bedrock.staging.test".to_string(), port))
+            .timeout(7000)?
+            .resolver(resolver)?
+            .ping()
+            .await?;
+        assert_eq!(status.motd, "Geyser Proxy");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_stats() -> anyhow::Result<()> {
+    use crate::query::QueryConnection;
+    use tokio::net::UdpSocket;
+
+    let server = UdpSocket::bind("127.0.0.1:0").await?;
+    let port = server.local_addr()?.port();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 64];
+        loop {
+            let (n, peer) = server.recv_from(&mut buf).await.unwrap();
+            let mut reply = vec![buf[2]];
+            reply.extend_from_slice(&buf[3..7]);
+            match (buf[2], n) {
+                (0x09, _) => reply.extend_from_slice(b"9513307\x00"),
+                (0x00, 11) => {
+                    reply.extend_from_slice(b"A Minecraft Server\x00SMP\x00world\x002\x0020\x00");
+                    reply.extend_from_slice(&25565u16.to_le_bytes());
+                    reply.extend_from_slice(b"127.0.0.1\x00");
+                }
+                _ => {
+                    reply.extend_from_slice(b"splitnum\x00\x80\x00");
+                    reply.extend_from_slice(b"hostname\x00A Minecraft Server\x00gametype\x00SMP\x00game_id\x00MINECRAFT\x00");
+                    reply.extend_from_slice(b"version\x001.21.4\x00plugins\x00Paper on 1.21.4: WorldEdit 7.3.9; LuckPerms 5.4\x00");
+                    reply.extend_from_slice(b"map\x00world\x00numplayers\x002\x00maxplayers\x0020\x00hostport\x0025565\x00hostip\x00127.0.0.1\x00\x00");
+                    reply.extend_from_slice(b"\x01player_\x00\x00Notch\x00jeb_\x00\x00");
+                }
+            }
+            server.send_to(&reply, peer).await.unwrap();
+        }
+    });
+
+    let conn = QueryConnection::new(("127.0.0.1".to_string(), port)).timeout(7000)?;
+    let basic = conn.basic_stat().await?;
+    assert_eq!((basic.map.as_str(), basic.online, basic.host_port), ("world", 2, 25565));
+
+    let full = conn.full_stat().await?;
+    assert_eq!(full.server_software.as_deref(), Some("Paper on 1.21.4"));
+    assert_eq!(full.plugins, vec!["WorldEdit 7.3.9", "LuckPerms 5.4"]);
+    assert_eq!(full.players, vec!["Notch", "jeb_"]);
+
+    // A resolver that never answers runs into the timeout
+    #[cfg(feature = "resolve")]
+    {
+        use std::sync::Arc;
+        use crate::resolver::{ResolveFuture, Resolver};
+
+        struct Hanging;
+        impl Resolver for Hanging {
+            fn resolve<'a>(&'a self, _host: &'a str) -> ResolveFuture<'a> {
+                Box::pin(std::future::pending())
+            }
+        }
+
+        let conn = QueryConnection::new(("query.staging.test".to_string(), port)).timeout(300)?.resolver(Arc::new(Hanging))?;
+        let started = std::time::Instant::now();
+        assert!(conn.basic_stat().await.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rcon_multi_packet_response() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    use crate::packets::RconPacket;
+    use crate::rcon::{RconConnection, RconError};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                while let Ok(len) = socket.read_i32_le().await {
+                    let mut buf = vec![0u8; len as usize];
+                    socket.read_exact(&mut buf).await.unwrap();
+                    let request = RconPacket::from(&buf).unwrap();
+                    let replies = match (request.packet_type, request.body.as_str()) {
+                        (3, "secret") => vec![RconPacket::new(request.request_id, 2, String::new())],
+                        (3, _) => vec![RconPacket::new(-1, 2, String::new())],
+                        // Split by bytes like vanilla, cutting a character in two
+                        (2, "lang") => {
+                            let output = "€".repeat(2000);
+                            output.as_bytes().chunks(4096)
+                                .map(|chunk| RconPacket::new(request.request_id, 0, String::from_utf8_lossy(chunk).to_string()))
+                                .collect()
+                        }
+                        // Split by characters, 3 bytes each
+                        (2, "motd") => {
+                            let output: Vec<char> = "€".repeat(5000).chars().collect();
+                            output.chunks(4096)
+                                .map(|chunk| RconPacket::new(request.request_id, 0, chunk.iter().collect()))
+                                .collect()
+                        }
+                        (2, _) => {
+                            let output = "x".repeat(5000);
+                            vec![
+                                RconPacket::new(request.request_id, 0, output[..4096].to_string()),
+                                RconPacket::new(request.request_id, 0, output[4096..].to_string()),
+                            ]
+                        }
+                        _ => vec![RconPacket::new(request.request_id, 0, "Unknown request 0".to_string())],
+                    };
+                    for reply in replies {
+                        socket.write_all(&reply.to_bytes()).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    let conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let mut rcon = RconConnection::new(conn);
+    let err = rcon.authenticate("wrong").await.unwrap_err();
+    assert_eq!(err.downcast_ref::<RconError>(), Some(&RconError::AuthenticationFailed));
+
+    let conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let mut rcon = RconConnection::new(conn);
+    rcon.authenticate("secret").await?;
+    assert_eq!(rcon.command("list").await?.len(), 5000);
+
+    let output = rcon.command("lang").await?;
+    assert_eq!(output.chars().filter(|&c| c == '\u{FFFD}').count(), 3);
+    assert_eq!(output.chars().filter(|&c| c == '€').count(), 1999);
+    assert_eq!(rcon.command("motd").await?, "€".repeat(5000));
+    Ok(())
+}
+
+/// Spawns a stand-in SOCKS5 proxy on an ephemeral localhost port for one
+/// connection. If `credentials` are set it only offers username/password
+/// authentication; once connected it answers like [`serve_status`].
+#[cfg(test)]
+async fn spawn_socks5_server(credentials: Option<(&'static str, &'static str)>, json: String) -> anyhow::Result<u16> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut header = [0u8; 2];
+        socket.read_exact(&mut header).await.unwrap();
+        let mut methods = vec![0u8; header[1] as usize];
+        socket.read_exact(&mut methods).await.unwrap();
+
+        let method = if credentials.is_some() { 0x02 } else { 0x00 };
+        if !methods.contains(&method) {
+            socket.write_all(&[0x05, 0xFF]).await.unwrap();
+            return;
+        }
+        socket.write_all(&[0x05, method]).await.unwrap();
+
+        if let Some((username, password)) = credentials {
+            let mut version_len = [0u8; 2];
+            socket.read_exact(&mut version_len).await.unwrap();
+            let mut user = vec![0u8; version_len[1] as usize];
+            socket.read_exact(&mut user).await.unwrap();
+            let pass_len = socket.read_u8().await.unwrap();
+            let mut pass = vec![0u8; pass_len as usize];
+            socket.read_exact(&mut pass).await.unwrap();
+            let accepted = user == username.as_bytes() && pass == password.as_bytes();
+            socket.write_all(&[0x01, if accepted { 0x00 } else { 0x01 }]).await.unwrap();
+            if !accepted {
+                return;
+            }
+        }
+
+        // CONNECT request: version, command, reserved, address type, address, port
+        let mut request = [0u8; 4];
+        socket.read_exact(&mut request).await.unwrap();
+        let addr_len = match request[3] {
+            0x01 => 4,
+            0x04 => 16,
+            _ => socket.read_u8().await.unwrap() as usize,
+        };
+        let mut addr = vec![0u8; addr_len + 2];
+        socket.read_exact(&mut addr).await.unwrap();
+        socket.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await.unwrap();
+
+        serve_status(socket, json, usize::MAX).await;
+    });
+    Ok(port)
+}
+
+/// Spawns a stand-in SOCKS4/4a proxy on an ephemeral localhost port for one
+/// connection. The requested address is sent back to the test: the host name
+/// for SOCKS4a, the IPv4 address otherwise. Requests with the user id
+/// "blocked" are rejected; others are answered like [`serve_status`].
+#[cfg(test)]
+async fn spawn_socks4_server(json: String) -> anyhow::Result<(u16, tokio::sync::oneshot::Receiver<String>)> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn read_null_terminated(socket: &mut TcpStream) -> String {
+        let mut bytes = Vec::new();
+        loop {
+            match socket.read_u8().await.unwrap() {
+                0 => return String::from_utf8(bytes).unwrap(),
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // version, command, port, IPv4 address, user id
+        let mut request = [0u8; 8];
+        socket.read_exact(&mut request).await.unwrap();
+        let user_id = read_null_terminated(&mut socket).await;
+        let ip = std::net::Ipv4Addr::new(request[4], request[5], request[6], request[7]);
+        // SOCKS4a marks a host name following the user id with 0.0.0.x
+        let target = if request[4..7] == [0, 0, 0] && request[7] != 0 {
+            read_null_terminated(&mut socket).await
+        } else {
+            ip.to_string()
+        };
+        tx.send(target).unwrap();
+
+        if user_id == "blocked" {
+            socket.write_all(&[0x00, 0x5D, 0, 0, 0, 0, 0, 0]).await.unwrap();
+            return;
+        }
+        socket.write_all(&[0x00, 0x5A, 0, 0, 0, 0, 0, 0]).await.unwrap();
+        serve_status(socket, json, usize::MAX).await;
+    });
+    Ok((port, rx))
+}
+
+/// Spawns a stand-in server that reads a handshake and one more packet, then
+/// answers with the packet body `reply`. The handshake body is sent back to the test.
+#[cfg(test)]
+async fn spawn_login_server(reply: Vec<u8>) -> anyhow::Result<(u16, tokio::sync::oneshot::Receiver<Vec<u8>>)> {
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let handshake = read_frame(&mut socket).await.unwrap();
+        read_frame(&mut socket).await.unwrap();
+        let _ = tx.send(handshake);
+
+        let mut frame = Vec::new();
+        varint::VarIntWrite::write_var_int(&mut frame, varint::VarInt::from(reply.len() as i32)).unwrap();
+        frame.extend_from_slice(&reply);
+        socket.write_all(&frame).await.unwrap();
+    });
+    Ok((port, rx))
+}
+
+/// Spawns a stand-in DNS server answering one query with SRV `records`
+/// of `(priority, weight, port, target)`.
+#[cfg(all(test, feature = "resolve"))]
+async fn spawn_dns_server(records: Vec<(u16, u16, u16, &'static str)>) -> anyhow::Result<std::net::SocketAddr> {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+    let addr = socket.local_addr()?;
+    tokio::spawn(async move {
+        let mut query = [0u8; 512];
+        let (len, peer) = socket.recv_from(&mut query).await.unwrap();
+
+        // Forged answers with the right id come first: one that is not a
+        // response, and one for another name
+        let mut forged = query[..2].to_vec();
+        forged.extend_from_slice(&[0x01, 0x80, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        forged.extend_from_slice(&query[12..len]);
+        socket.send_to(&forged, peer).await.unwrap();
+        forged[2] = 0x81;
+        forged[13] = b'x';
+        socket.send_to(&forged, peer).await.unwrap();
+
+        let mut response = query[..2].to_vec();
+        response.extend_from_slice(&[0x81, 0x80, 0x00, 0x01, 0x00, records.len() as u8, 0x00, 0x00, 0x00, 0x00]);
+        response.extend_from_slice(&query[12..len]);
+        for (priority, weight, port, target) in records {
+            let mut data = [priority.to_be_bytes(), weight.to_be_bytes(), port.to_be_bytes()].concat();
+            for label in target.split('.') {
+                data.push(label.len() as u8);
+                data.extend_from_slice(label.as_bytes());
+            }
+            data.push(0);
+            // Owner name as a pointer to the question, type SRV, class IN, TTL 60
+            response.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C]);
+            response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            response.extend_from_slice(&data);
+        }
+        socket.send_to(&response, peer).await.unwrap();
+    });
+    Ok(addr)
+}
+
+#[cfg(feature = "resolve")]
+#[tokio::test]
+async fn test_srv_resolution() -> anyhow::Result<()> {
+    use crate::connection::{Connection, LoginProbe};
+    use crate::dns::{build_query, is_answer_to, order_records, parse_resolv_conf, parse_response, SrvRecord};
+
+    let (port, handshake) = spawn_login_server(vec![0x03, 0x80, 0x02]).await?;
+    let dns = spawn_dns_server(vec![(10, 0, 1, "backup.invalid"), (0, 5, port, "127.0.0.1")]).await?;
+    let mut conn = Connection::new(("mc.example.test".to_string(), 25565))
+        .nameserver(dns)?
+        .timeout(7000)?
+        .connect().await?;
+    assert_eq!(conn.login_probe("Notch").await?, LoginProbe::OfflineMode { compression_threshold: Some(256) });
+    // The handshake names the host the player typed, not the SRV target
+    let handshake = handshake.await?;
+    assert!(handshake.windows(15).any(|window| window == b"mc.example.test"));
+    assert_eq!(&handshake[handshake.len() - 3..], &[0x63, 0xDD, 0x02]);
+
+    let record = |priority, weight, target: &str| SrvRecord { priority, weight, port: 25565, target: target.to_string() };
+    let ordered = order_records(
+        vec![record(1, 0, "d"), record(0, 10, "a"), record(0, 30, "b"), record(0, 0, "c")],
+        // Drawing the largest value picks the last record with a non-zero weight
+        |bound| bound - 1,
+    );
+    let targets: Vec<_> = ordered.iter().map(|record| record.target.as_str()).collect();
+    assert_eq!(targets, vec!["b", "a", "c", "d"]);
+
+    // Target "srv" followed by a pointer to "example.test" in the question
+    let mut message = vec![0x00, 0x01, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+    message.extend_from_slice(b"\x0a_minecraft\x04_tcp\x02mc\x07example\x04test\x00\x00\x21\x00\x01");
+    message.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C, 0x00, 0x0C]);
+    message.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x63, 0xDD, 0x03, b's', b'r', b'v', 0xC0, 31]);
+    let srv = SrvRecord { priority: 0, weight: 0, port: 25565, target: "srv.example.test".to_string() };
+    assert_eq!(parse_response(&message)?, (vec![srv], Some(60)));
+    message[2] = 0x81;
+    message[3] = 0x83;
+    assert_eq!(parse_response(&message)?, (Vec::new(), None));
+
+    // Answers must echo the question; nameservers may change its case
+    let query = build_query(1, "_minecraft._tcp.mc.example.test")?;
+    message[14] = b'M';
+    assert!(is_answer_to(&message, &query));
+    message[2] = 0x01;
+    assert!(!is_answer_to(&message, &query));
+    assert!(!is_answer_to(&message[..20], &query));
+
+    assert_eq!(parse_resolv_conf("# local\nsearch lan\nnameserver fe80::1%eth0\nnameserver 1.1.1.1\n"), Some("[fe80::1]:53".parse()?));
+
+    // The SRV lookup and the attempts on every target share one timeout: a
+    // proxy that never answers must not stretch it to one timeout per target
+    let silent_proxy = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_port = silent_proxy.local_addr()?.port();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((socket, _)) = silent_proxy.accept().await {
+            held.push(socket);
+        }
+    });
+    let dns = spawn_dns_server(vec![(0, 0, 1, "a.invalid"), (1, 0, 2, "b.invalid"), (2, 0, 3, "c.invalid")]).await?;
+    let started = std::time::Instant::now();
+    let result = Connection::new(("mc.example.test".to_string(), 25565))
+        .nameserver(dns)?
+        .timeout(500)?
+        .proxy_socks5(("127.0.0.1".to_string(), proxy_port))?
+        .connect().await;
+    assert!(result.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_millis(1000), "took {:?}", started.elapsed());
+
+    // Neither does a nameserver that never answers
+    let dead_dns = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+    let started = std::time::Instant::now();
+    let result = Connection::new(("localhost".to_string(), 25565))
+        .nameserver(dead_dns.local_addr()?)?
+        .timeout(500)?
+        .proxy_socks5(("127.0.0.1".to_string(), proxy_port))?
+        .connect().await;
+    assert!(result.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_millis(1000), "took {:?}", started.elapsed());
+    Ok(())
+}
+
+#[cfg(feature = "resolve")]
+#[tokio::test]
+async fn test_happy_eyeballs() -> anyhow::Result<()> {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use crate::connection::{connect_happy_eyeballs, interleave_families, Connection};
+
+    let addrs: Vec<SocketAddr> = ["[::1]:1", "[::1]:2", "[::1]:3", "127.0.0.1:4", "127.0.0.1:5"]
+        .iter()
+        .map(|addr| addr.parse())
+        .collect::<Result<_, _>>()?;
+    let ordered: Vec<u16> = interleave_families(addrs).iter().map(SocketAddr::port).collect();
+    assert_eq!(ordered, [1, 4, 2, 5, 3]);
+
+    // A port nobody listens on, refusing connections
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let open = listener.local_addr()?;
+
+    let (_, used) = connect_happy_eyeballs(vec![closed, open], Duration::from_secs(5)).await?;
+    assert_eq!(used, open);
+    assert!(connect_happy_eyeballs(vec![closed], Duration::from_secs(5)).await.is_err());
+    assert!(connect_happy_eyeballs(Vec::new(), Duration::from_secs(5)).await.is_err());
+
+    // "localhost" may resolve to ::1 first, where nothing listens
+    let conn = Connection::new(("localhost".to_string(), open.port())).timeout(7000)?.connect().await?;
+    assert_eq!(conn.peer_addr, Some(open));
+    Ok(())
+}
+
+#[cfg(feature = "resolve")]
+#[tokio::test]
+async fn test_resolvers() -> anyhow::Result<()> {
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::connection::Connection;
+    use std::net::SocketAddr;
+    use crate::dns::SrvRecord;
+    use crate::resolver::{CachingResolver, Lookup, ResolveFuture, Resolver, SrvFuture, SrvLookup, StaticResolver};
+
+    struct Counting(AtomicUsize, Option<Duration>);
+    impl Resolver for Counting {
+        fn resolve<'a>(&'a self, _host: &'a str) -> ResolveFuture<'a> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let ttl = self.1;
+            Box::pin(async move { Ok(Lookup { addrs: vec!["127.0.0.1".parse()?], ttl }) })
+        }
+
+        fn resolve_srv<'a>(&'a self, _host: &'a str, _nameserver: Option<SocketAddr>) -> SrvFuture<'a> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let ttl = self.1;
+            let records = vec![SrvRecord { priority: 0, weight: 0, port: 25566, target: "srv.example.test".to_string() }];
+            Box::pin(async move { Ok(SrvLookup { records, ttl }) })
+        }
+    }
+
+    let upstream = Arc::new(Counting(AtomicUsize::new(0), None));
+    let cache = CachingResolver::new(upstream.clone());
+    cache.resolve("Play.Example.com").await?;
+    let lookup = cache.resolve("play.example.com.").await?;
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 1);
+    assert!(lookup.ttl.is_some_and(|ttl| ttl <= Duration::from_secs(60)));
+    cache.clear();
+    cache.resolve("play.example.com").await?;
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 2);
+
+    // SRV answers are cached per host and nameserver
+    let nameserver: SocketAddr = "127.0.0.1:53".parse()?;
+    let srv = cache.resolve_srv("play.example.com", Some(nameserver)).await?;
+    assert_eq!(srv.records[0].port, 25566);
+    assert_eq!(cache.resolve_srv("PLAY.example.com.", Some(nameserver)).await?.records, srv.records);
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 3);
+    cache.resolve_srv("play.example.com", None).await?;
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 4);
+
+    // A zero TTL from upstream is respected
+    let upstream = Arc::new(Counting(AtomicUsize::new(0), Some(Duration::ZERO)));
+    let cache = CachingResolver::new(upstream.clone()).default_ttl(Duration::from_secs(3600));
+    cache.resolve("play.example.com").await?;
+    cache.resolve("play.example.com").await?;
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 2);
+
+    let upstream = Arc::new(Counting(AtomicUsize::new(0), None));
+    let hosts = StaticResolver::new(upstream.clone())
+        .hosts("# staging\n10.0.0.5 staging.example.com lobby.example.com\nnot-an-ip broken\n")
+        .host("STAGING.example.com", "::1".parse()?);
+    let expected: Vec<IpAddr> = vec!["10.0.0.5".parse()?, "::1".parse()?];
+    assert_eq!(hosts.resolve("staging.example.com").await?.addrs, expected);
+    hosts.resolve("broken").await?;
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 1);
+
+    // Overridden hosts have no SRV records; others ask the fallback
+    assert!(hosts.resolve_srv("lobby.example.com", None).await?.records.is_empty());
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 1);
+    assert_eq!(hosts.resolve_srv("play.example.com", None).await?.records.len(), 1);
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 2);
+
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":3},"description":"A Minecraft Server"}"#;
+    let port = spawn_status_server(json.to_string(), usize::MAX).await?;
+    let resolver = Arc::new(StaticResolver::new(upstream.clone()).host("mc.staging.test", "127.0.0.1".parse()?));
+    let mut conn = Connection::new(("mc.staging.test".to_string(), port)).timeout(7000)?.resolver(resolver)?.connect().await?;
+    assert_eq!(conn.ping().await?.players.online, 3);
+    assert_eq!(upstream.0.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_socks5_auth() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    use crate::proxy::ProxyError;
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":3},"description":"A Minecraft Server"}"#;
+    let target = ("127.0.0.1".to_string(), 25565);
+
+    let port = spawn_socks5_server(Some(("user", "secret")), json.to_string()).await?;
+    let mut conn = Connection::new(target.clone())
+        .timeout(7000)?
+        .proxy_socks5_auth(("127.0.0.1".to_string(), port), "user", "secret")?
+        .connect().await?;
+    assert_eq!(conn.ping().await?.players.online, 3);
+
+    let port = spawn_socks5_server(Some(("user", "secret")), json.to_string()).await?;
+    let err = Connection::new(target.clone())
+        .proxy_socks5_auth(("127.0.0.1".to_string(), port), "user", "wrong")?
+        .connect().await.err().unwrap();
+    assert_eq!(err.downcast_ref::<ProxyError>(), Some(&ProxyError::AuthenticationFailed));
+
+    let port = spawn_socks5_server(Some(("user", "secret")), json.to_string()).await?;
+    let err = Connection::new(target.clone())
+        .proxy_socks5(("127.0.0.1".to_string(), port))?
+        .connect().await.err().unwrap();
+    assert_eq!(err.downcast_ref::<ProxyError>(), Some(&ProxyError::AuthenticationRequired));
+
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+    let err = Connection::new(target.clone())
+        .proxy_socks5_auth(("127.0.0.1".to_string(), closed), "user", "secret")?
+        .connect().await.err().unwrap();
+    assert!(matches!(err.downcast_ref::<ProxyError>(), Some(ProxyError::Unreachable(_))));
+
+    assert!(Connection::new(target).proxy_socks5_auth(("127.0.0.1".to_string(), 1080), "", "secret").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_socks4_proxies() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+    use crate::proxy::{Proxy, ProxyError};
+    let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":3},"description":"A Minecraft Server"}"#;
+    let socks4 = |port: u16, user_id: Option<&str>| Proxy::Socks4 {
+        addr: ("127.0.0.1".to_string(), port),
+        user_id: user_id.map(str::to_string),
+    };
+
+    let (port, target) = spawn_socks4_server(json.to_string()).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), 25565))
+        .timeout(7000)?
+        .proxy(socks4(port, None))?
+        .connect().await?;
+    assert_eq!(conn.ping().await?.players.online, 3);
+    assert_eq!(target.await?, "127.0.0.1");
+
+    let (port, target) = spawn_socks4_server(json.to_string()).await?;
+    let err = Connection::new(("127.0.0.1".to_string(), 25565))
+        .proxy(socks4(port, Some("blocked")))?
+        .connect().await.err().unwrap();
+    assert_eq!(err.downcast_ref::<ProxyError>(), Some(&ProxyError::AuthenticationFailed));
+    assert_eq!(target.await?, "127.0.0.1");
+
+    // SOCKS4 cannot carry IPv6, so the proxy is not even contacted
+    assert!(Connection::new(("::1".to_string(), 25565)).proxy(socks4(1, None))?.connect().await.is_err());
+    assert!(Connection::new(("::1".to_string(), 25565)).proxy(socks4(1, Some(""))).is_err());
+
+    // SOCKS4a sends the host name to the proxy, with or without "resolve"
+    let (port, target) = spawn_socks4_server(json.to_string()).await?;
+    let mut conn = Connection::new(("mc.staging.test".to_string(), 25566))
+        .timeout(7000)?
+        .proxy(Proxy::Socks4a { addr: ("127.0.0.1".to_string(), port), user_id: Some("mc-ping".to_string()) })?
+        .connect().await?;
+    assert_eq!(conn.ping().await?.players.max, 20);
+    assert_eq!(target.await?, "mc.staging.test");
+
+    #[cfg(not(feature = "resolve"))]
+    {
+        let err = Connection::new(("mc.staging.test".to_string(), 25566)).proxy(socks4(1, None))?.connect().await.err().unwrap();
+        assert!(err.to_string().contains("resolve"), "{}", err);
+    }
+
+    #[cfg(feature = "resolve")]
+    {
+        use std::sync::Arc;
+        use crate::resolver::{StaticResolver, SystemResolver};
+
+        // Plain SOCKS4 resolves the name locally
+        let (port, target) = spawn_socks4_server(json.to_string()).await?;
+        let resolver = Arc::new(StaticResolver::new(SystemResolver).host("mc.staging.test", "127.0.0.1".parse()?));
+        let mut conn = Connection::new(("mc.staging.test".to_string(), 25566))
+            .timeout(7000)?
+            .resolver(resolver)?
+            .proxy(socks4(port, None))?
+            .connect().await?;
+        assert_eq!(conn.ping().await?.players.max, 20);
+        assert_eq!(target.await?, "127.0.0.1");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_login_probe() -> anyhow::Result<()> {
+    use crate::connection::{Connection, LoginProbe};
+
+    let reason = r#"{"text":"You are not whitelisted on this server!"}"#;
+    let mut reply = vec![0x00, reason.len() as u8];
+    reply.extend_from_slice(reason.as_bytes());
+    let (port, handshake) = spawn_login_server(reply).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let probe = conn.login_probe("Notch").await?;
+    assert_eq!(handshake.await?.last(), Some(&0x02));
+    assert!(matches!(probe, LoginProbe::Disconnected { ref reason } if reason["text"].as_str().unwrap().contains("whitelisted")));
+
+    let (port, _) = spawn_login_server(vec![0x03, 0x80, 0x02]).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    assert_eq!(conn.login_probe("Notch").await?, LoginProbe::OfflineMode { compression_threshold: Some(256) });
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compressed_login_packets() -> anyhow::Result<()> {
+    use crate::connection::{Connection, LoginProbe};
+    use crate::framing::{decode_frame, encode_frame};
+    use crate::packets::{LoginResponse, LoginStart, Packet};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        read_frame(&mut socket).await.unwrap();
+        read_frame(&mut socket).await.unwrap();
+        socket.write_all(&encode_frame(&[0x03, 0x10], None).unwrap()).await.unwrap();
+
+        let mut success = vec![0x02];
+        success.extend_from_slice(&7u128.to_be_bytes());
+        success.extend_from_slice(&[0x05, b'N', b'o', b't', b'c', b'h', 0x00]);
+        socket.write_all(&encode_frame(&success, Some(16)).unwrap()).await.unwrap();
+
+        // Once compression is on, Login Start uses the compressed framing too
+        decode_frame(&read_frame(&mut socket).await.unwrap(), Some(16)).unwrap();
+        let login = decode_frame(&read_frame(&mut socket).await.unwrap(), Some(16)).unwrap();
+        assert_eq!(LoginStart::from_body(&login).unwrap().username, "Notch");
+        socket.write_all(&encode_frame(&[0x03, 0x20], Some(16)).unwrap()).await.unwrap();
+    });
+
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    assert_eq!(conn.login_probe("Notch").await?, LoginProbe::OfflineMode { compression_threshold: Some(16) });
+    assert_eq!(conn.codec.compression_threshold(), Some(16));
+    let success = LoginResponse::from(&conn.read_packet().await?, conn.protocol_version)?;
+    assert_eq!(success, LoginResponse::LoginSuccess { uuid: 7, username: "Notch".to_string() });
+    assert_eq!(conn.login_probe("Notch").await?, LoginProbe::OfflineMode { compression_threshold: Some(32) });
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+#[tokio::test]
+async fn test_encrypted_login() -> anyhow::Result<()> {
+    use crate::connection::{Connection, LoginProbe};
+    use crate::encryption::{generate_shared_secret, DecryptReader, Encryption};
+    use crate::framing::encode_frame;
+    use crate::packets::LoginResponse;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    let private_key = RsaPrivateKey::new(&mut rand_core::OsRng, 1024)?;
+    let public_key = private_key.to_public_key().to_public_key_der()?.as_bytes().to_vec();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server_key = public_key.clone();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        read_frame(&mut socket).await.unwrap();
+        read_frame(&mut socket).await.unwrap();
+
+        let mut request = vec![0x01, 0x00];
+        varint::VarIntWrite::write_var_int(&mut request, varint::VarInt::from(server_key.len() as i32)).unwrap();
+        request.extend_from_slice(&server_key);
+        request.extend_from_slice(&[0x04, 1, 2, 3, 4, 0x01]);
+        socket.write_all(&encode_frame(&request, None).unwrap()).await.unwrap();
+
+        let response = read_frame(&mut socket).await.unwrap();
+        let mut cursor = &response[1..];
+        let secret_len = i32::from(varint::VarIntRead::read_var_int(&mut cursor).unwrap()) as usize;
+        let secret = private_key.decrypt(Pkcs1v15Encrypt, &cursor[..secret_len]).unwrap();
+        let secret: [u8; 16] = secret.try_into().unwrap();
+        let mut encryption = Encryption::new(&secret);
+
+        let ping = read_frame(&mut DecryptReader::new(&mut socket, &mut encryption)).await.unwrap();
+        let mut success = vec![0x02];
+        success.extend_from_slice(&1u128.to_be_bytes());
+        success.extend_from_slice(&[0x05, b'N', b'o', b't', b'c', b'h', 0x00]);
+        success.extend_from_slice(&ping);
+        let mut frame = encode_frame(&success, None).unwrap();
+        encryption.encrypt(&mut frame);
+        socket.write_all(&frame).await.unwrap();
+    });
+
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let (key, token) = match conn.login_probe("Notch").await? {
+        LoginProbe::OnlineMode { public_key, verify_token, .. } => (public_key, verify_token),
+        other => panic!("expected online mode, got {:?}", other),
+    };
+    assert_eq!((key, token.clone()), (public_key.clone(), vec![1, 2, 3, 4]));
+
+    conn.send_encryption_response(&public_key, &token, &generate_shared_secret()).await?;
+    conn.write_packet(&[0x7F]).await?;
+    let success = conn.read_packet().await?;
+    assert_eq!(success.last(), Some(&0x7F));
+    assert!(matches!(
+        LoginResponse::from(&success[..success.len() - 1], conn.protocol_version)?,
+        LoginResponse::LoginSuccess { uuid: 1, .. }
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_transfer_probe() -> anyhow::Result<()> {
+    use crate::connection::Connection;
+
+    let reason = r#"{"translate":"multiplayer.disconnect.transfers_disabled"}"#;
+    let mut reply = vec![0x00, reason.len() as u8];
+    reply.extend_from_slice(reason.as_bytes());
+    let (port, handshake) = spawn_login_server(reply).await?;
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.connect().await?;
+    let probe = conn.transfer_probe("Notch").await?;
+    assert_eq!(handshake.await?.last(), Some(&0x03));
+    assert!(!probe.is_accepted());
+
+    // rejected before anything is sent
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let received = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut socket, &mut buf).await.unwrap();
+        buf
+    });
+    let mut conn = Connection::new(("127.0.0.1".to_string(), port)).timeout(7000)?.protocol_version(765)?.connect().await?;
+    let err = conn.transfer_probe("Notch").await.unwrap_err();
+    assert!(err.to_string().contains("Transfer intent needs protocol 766"), "{}", err);
+    drop(conn);
+    assert!(received.await?.is_empty());
     Ok(())
-}
\ No newline at end of file
+}
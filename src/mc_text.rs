@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use serde::Deserialize;
 
 /// Structure for the Minecraft server status response.
@@ -53,6 +54,47 @@ pub enum Description {
     Complex(serde_json::Value),
 }
 
+impl Description {
+    /// Decodes a text component sent as NBT, as 1.20.3+ servers do outside the status JSON.
+    ///
+    /// # Errors
+    /// Returns an error if the tag is not a valid text component.
+    ///
+    /// NBT has no booleans, so the style flags (`bold`, `italic`, ...) arrive
+    /// as bytes; they are turned back into JSON booleans, here and in every
+    /// child component.
+    pub fn from_nbt(tag: crate::nbt::Tag) -> anyhow::Result<Description> {
+        match crate::nbt::from_tag(tag)? {
+            Description::Complex(mut value) => {
+                flags_to_booleans(&mut value);
+                Ok(Description::Complex(value))
+            }
+            text => Ok(text),
+        }
+    }
+}
+
+/// Boolean fields of text components, sent as TAG_Byte in NBT.
+const TEXT_COMPONENT_FLAGS: [&str; 6] = ["bold", "italic", "underlined", "strikethrough", "obfuscated", "interpret"];
+
+/// Replaces numeric [`TEXT_COMPONENT_FLAGS`] in a component and its children with booleans.
+fn flags_to_booleans(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(entries) => {
+            for (key, entry) in entries.iter_mut() {
+                match entry.as_i64() {
+                    Some(flag) if TEXT_COMPONENT_FLAGS.contains(&key.as_str()) => {
+                        *entry = serde_json::Value::Bool(flag != 0);
+                    }
+                    _ => flags_to_booleans(entry),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(flags_to_booleans),
+        _ => {}
+    }
+}
+
 /// Player information.
 #[derive(Debug, Deserialize)]
 pub struct Players {
@@ -86,3 +128,116 @@ pub struct Mod {
     /// Mod name.
     pub name: String,
 }
+
+/// Bedrock Edition server status, parsed from a RakNet Unconnected Pong.
+///
+/// The pong carries a `;`-separated string:
+/// `MCPE;motd;protocol;version;online;max;serverId;subMotd;gamemode;gamemodeId;portV4;portV6;`.
+/// Fields after `max` are optional, older servers omit them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedrockStatus {
+    /// Edition, "MCPE" for Bedrock or "MCEE" for Education Edition.
+    pub edition: String,
+
+    /// First MOTD line.
+    pub motd: String,
+
+    /// Protocol version number, e.g. 766.
+    pub protocol: i32,
+
+    /// Version name, e.g. "1.21.50".
+    pub version: String,
+
+    /// Current number of online players.
+    pub online: i32,
+
+    /// Maximum number of players allowed on the server.
+    pub max: i32,
+
+    /// Server unique id from the pong string.
+    pub server_id: Option<String>,
+
+    /// Second MOTD line, usually the level name.
+    pub sub_motd: Option<String>,
+
+    /// Game mode name, e.g. "Survival".
+    pub game_mode: Option<String>,
+
+    /// Numeric game mode.
+    pub game_mode_id: Option<i32>,
+
+    /// IPv4 port the server listens on.
+    pub port_v4: Option<u16>,
+
+    /// IPv6 port the server listens on.
+    pub port_v6: Option<u16>,
+}
+
+/// Result of a Query protocol basic stat request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBasicStat {
+    /// Message of the day.
+    pub motd: String,
+
+    /// Game type, always "SMP" for vanilla servers.
+    pub game_type: String,
+
+    /// Name of the default world.
+    pub map: String,
+
+    /// Current number of online players.
+    pub online: i32,
+
+    /// Maximum number of players allowed on the server.
+    pub max: i32,
+
+    /// Port the server listens on.
+    pub host_port: u16,
+
+    /// IP address the server listens on.
+    pub host_ip: String,
+}
+
+/// Result of a Query protocol full stat request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryFullStat {
+    /// Message of the day (`hostname`).
+    pub motd: String,
+
+    /// Game type, always "SMP" for vanilla servers.
+    pub game_type: String,
+
+    /// Game id, always "MINECRAFT" for vanilla servers.
+    pub game_id: String,
+
+    /// Server version, e.g. "1.21.4".
+    pub version: String,
+
+    /// Server software reported in the plugin list, e.g. "Paper on 1.21.4".
+    /// Absent for vanilla servers.
+    pub server_software: Option<String>,
+
+    /// Installed plugins, empty for vanilla servers.
+    pub plugins: Vec<String>,
+
+    /// Name of the default world.
+    pub map: String,
+
+    /// Current number of online players.
+    pub online: i32,
+
+    /// Maximum number of players allowed on the server.
+    pub max: i32,
+
+    /// Port the server listens on.
+    pub host_port: u16,
+
+    /// IP address the server listens on.
+    pub host_ip: String,
+
+    /// Names of all online players.
+    pub players: Vec<String>,
+
+    /// Key/value pairs not covered by the fields above.
+    pub extra: HashMap<String, String>,
+}
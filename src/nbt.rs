@@ -0,0 +1,481 @@
+//! NBT (Named Binary Tag) reader and writer.
+//!
+//! Two root formats exist on the wire: the classic named root (tag id, name,
+//! payload), used on disk and before 1.20.2, and network NBT (tag id,
+//! payload), sent by 1.20.2+ servers. Since 1.20.3 text components are sent as
+//! network NBT, with any tag type as root: a plain text is a lone String tag.
+//!
+//! Strings are length-prefixed modified UTF-8 (Java's `DataOutput.writeUTF`).
+//!
+//! [`Tag`] is a serde `Deserializer`, so [`from_tag`] decodes it into any
+//! serde type, reading TAG_Byte as `bool` where one is expected. Text
+//! components decode into the same
+//! [`Description`](crate::mc_text::Description) as in the status JSON.
+//!
+//! # Example
+//! ```
+//! use mc_ping::nbt::{read_network, write_network, Tag};
+//! use mc_ping::mc_text::Description;
+//!
+//! let mut buf = Vec::new();
+//! write_network(&Tag::String("A Minecraft Server".to_string()), &mut buf)?;
+//! assert_eq!(&buf[..3], &[0x08, 0x00, 0x12]);
+//!
+//! let tag = read_network(&mut &buf[..])?.unwrap();
+//! assert!(matches!(Description::from_nbt(tag)?, Description::Text(text) if text == "A Minecraft Server"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Context, Result};
+use serde::de::value::{Error as DeError, MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{Number, Value};
+use crate::types::{Decode, Encode};
+
+/// Deepest nesting of lists and compounds accepted, as in vanilla.
+pub const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// An NBT tag.
+///
+/// TAG_End only terminates compounds and marks absent network NBT, so it has
+/// no variant here.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    /// TAG_Byte, also used for booleans.
+    Byte(i8),
+    /// TAG_Short.
+    Short(i16),
+    /// TAG_Int.
+    Int(i32),
+    /// TAG_Long.
+    Long(i64),
+    /// TAG_Float.
+    Float(f32),
+    /// TAG_Double.
+    Double(f64),
+    /// TAG_Byte_Array.
+    ByteArray(Vec<i8>),
+    /// TAG_String.
+    String(String),
+    /// TAG_List; all elements must have the same tag type.
+    List(Vec<Tag>),
+    /// TAG_Compound.
+    Compound(BTreeMap<String, Tag>),
+    /// TAG_Int_Array.
+    IntArray(Vec<i32>),
+    /// TAG_Long_Array.
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// Returns the tag type id.
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => TAG_BYTE,
+            Tag::Short(_) => TAG_SHORT,
+            Tag::Int(_) => TAG_INT,
+            Tag::Long(_) => TAG_LONG,
+            Tag::Float(_) => TAG_FLOAT,
+            Tag::Double(_) => TAG_DOUBLE,
+            Tag::ByteArray(_) => TAG_BYTE_ARRAY,
+            Tag::String(_) => TAG_STRING,
+            Tag::List(_) => TAG_LIST,
+            Tag::Compound(_) => TAG_COMPOUND,
+            Tag::IntArray(_) => TAG_INT_ARRAY,
+            Tag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Returns the value stored under `key` if this is a compound.
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    /// Writes the tag payload, without type id or name.
+    fn write_payload(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Tag::Byte(value) => value.encode(buf),
+            Tag::Short(value) => value.encode(buf),
+            Tag::Int(value) => value.encode(buf),
+            Tag::Long(value) => value.encode(buf),
+            Tag::Float(value) => value.encode(buf),
+            Tag::Double(value) => value.encode(buf),
+            Tag::ByteArray(values) => write_array(values, buf),
+            Tag::String(value) => write_mutf8(value, buf),
+            Tag::List(values) => {
+                let id = values.first().map_or(TAG_END, Tag::id);
+                if values.iter().any(|value| value.id() != id) {
+                    return Err(anyhow!("NBT list mixes tag types"));
+                }
+                buf.push(id);
+                write_len(values.len(), buf)?;
+                values.iter().try_for_each(|value| value.write_payload(buf))
+            }
+            Tag::Compound(entries) => {
+                for (name, value) in entries {
+                    buf.push(value.id());
+                    write_mutf8(name, buf)?;
+                    value.write_payload(buf)?;
+                }
+                buf.push(TAG_END);
+                Ok(())
+            }
+            Tag::IntArray(values) => write_array(values, buf),
+            Tag::LongArray(values) => write_array(values, buf),
+        }
+    }
+
+    /// Reads the payload of a tag of type `id` from the front of `buf`.
+    fn read_payload(id: u8, buf: &mut &[u8], depth: usize) -> Result<Tag> {
+        if depth > MAX_DEPTH {
+            return Err(anyhow!("NBT is nested deeper than {} levels", MAX_DEPTH));
+        }
+        let tag = match id {
+            TAG_BYTE => Tag::Byte(i8::decode(buf)?),
+            TAG_SHORT => Tag::Short(i16::decode(buf)?),
+            TAG_INT => Tag::Int(i32::decode(buf)?),
+            TAG_LONG => Tag::Long(i64::decode(buf)?),
+            TAG_FLOAT => Tag::Float(f32::decode(buf)?),
+            TAG_DOUBLE => Tag::Double(f64::decode(buf)?),
+            TAG_BYTE_ARRAY => Tag::ByteArray(read_array(buf)?),
+            TAG_STRING => Tag::String(read_mutf8(buf)?),
+            TAG_LIST => {
+                let element_id = u8::decode(buf)?;
+                let len = read_len(buf, 1)?;
+                if element_id == TAG_END && len > 0 {
+                    return Err(anyhow!("NBT list of TAG_End has {} elements", len));
+                }
+                let values = (0..len)
+                    .map(|_| Tag::read_payload(element_id, buf, depth + 1))
+                    .collect::<Result<_>>()?;
+                Tag::List(values)
+            }
+            TAG_COMPOUND => {
+                let mut entries = BTreeMap::new();
+                loop {
+                    let value_id = u8::decode(buf).context("NBT compound is not terminated")?;
+                    if value_id == TAG_END {
+                        break;
+                    }
+                    let name = read_mutf8(buf)?;
+                    entries.insert(name, Tag::read_payload(value_id, buf, depth + 1)?);
+                }
+                Tag::Compound(entries)
+            }
+            TAG_INT_ARRAY => Tag::IntArray(read_array(buf)?),
+            TAG_LONG_ARRAY => Tag::LongArray(read_array(buf)?),
+            _ => return Err(anyhow!("Unknown NBT tag type {}", id)),
+        };
+        Ok(tag)
+    }
+}
+
+/// Writes an array or list length as a signed 32-bit integer.
+fn write_len(len: usize, buf: &mut Vec<u8>) -> Result<()> {
+    i32::try_from(len)
+        .map_err(|_| anyhow!("NBT array of {} elements is too long", len))?
+        .encode(buf)
+}
+
+/// Reads an array or list length and checks that `len` elements of at least
+/// `element_size` bytes fit in what is left of `buf`.
+fn read_len(buf: &mut &[u8], element_size: usize) -> Result<usize> {
+    let len = i32::decode(buf)?;
+    if len < 0 || len as usize * element_size > buf.len() {
+        return Err(anyhow!("NBT length {} exceeds remaining size {}", len, buf.len()));
+    }
+    Ok(len as usize)
+}
+
+fn write_array<T: Encode>(values: &[T], buf: &mut Vec<u8>) -> Result<()> {
+    write_len(values.len(), buf)?;
+    values.iter().try_for_each(|value| value.encode(buf))
+}
+
+fn read_array<T: Decode>(buf: &mut &[u8]) -> Result<Vec<T>> {
+    let len = read_len(buf, size_of::<T>())?;
+    (0..len).map(|_| T::decode(buf)).collect()
+}
+
+/// Writes a string as modified UTF-8 with a u16 byte length.
+///
+/// Differs from UTF-8 in two ways: NUL is written as `C0 80`, and characters
+/// outside the BMP are written as two 3-byte surrogates.
+fn write_mutf8(value: &str, buf: &mut Vec<u8>) -> Result<()> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    u16::try_from(bytes.len())
+        .map_err(|_| anyhow!("NBT string of {} bytes is too long", bytes.len()))?
+        .encode(buf)?;
+    buf.extend(bytes);
+    Ok(())
+}
+
+/// Reads a modified UTF-8 string with a u16 byte length.
+fn read_mutf8(buf: &mut &[u8]) -> Result<String> {
+    let len = u16::decode(buf)? as usize;
+    if len > buf.len() {
+        return Err(anyhow!("NBT string length {} exceeds remaining size {}", len, buf.len()));
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+
+    let mut units = Vec::with_capacity(len);
+    let mut iter = bytes.iter().copied();
+    while let Some(first) = iter.next() {
+        let mut continuation = || match iter.next() {
+            Some(byte) if byte & 0xC0 == 0x80 => Ok(u16::from(byte & 0x3F)),
+            _ => Err(anyhow!("Invalid modified UTF-8 in NBT string")),
+        };
+        let unit = match first {
+            0x01..=0x7F => u16::from(first),
+            0xC0..=0xDF => (u16::from(first & 0x1F) << 6) | continuation()?,
+            0xE0..=0xEF => (u16::from(first & 0x0F) << 12) | (continuation()? << 6) | continuation()?,
+            _ => return Err(anyhow!("Invalid modified UTF-8 in NBT string")),
+        };
+        units.push(unit);
+    }
+    String::from_utf16(&units).context("Unpaired surrogate in NBT string")
+}
+
+/// Reads NBT with a named root (tag id, name, payload).
+///
+/// # Errors
+/// Returns an error if the NBT is truncated, malformed, nested too deep, or the root is TAG_End.
+pub fn read_named(buf: &mut &[u8]) -> Result<(String, Tag)> {
+    let id = u8::decode(buf)?;
+    if id == TAG_END {
+        return Err(anyhow!("Named NBT root cannot be TAG_End"));
+    }
+    let name = read_mutf8(buf)?;
+    Ok((name, Tag::read_payload(id, buf, 0)?))
+}
+
+/// Writes NBT with a named root (tag id, name, payload).
+///
+/// # Errors
+/// Returns an error if a string, array or list is too long, or a list mixes tag types.
+pub fn write_named(name: &str, tag: &Tag, buf: &mut Vec<u8>) -> Result<()> {
+    buf.push(tag.id());
+    write_mutf8(name, buf)?;
+    tag.write_payload(buf)
+}
+
+/// Reads network NBT (tag id, payload), as sent by 1.20.2+ servers.
+///
+/// Returns `None` for a lone TAG_End, which marks absent optional NBT.
+///
+/// # Errors
+/// Returns an error if the NBT is truncated, malformed or nested too deep.
+pub fn read_network(buf: &mut &[u8]) -> Result<Option<Tag>> {
+    match u8::decode(buf)? {
+        TAG_END => Ok(None),
+        id => Ok(Some(Tag::read_payload(id, buf, 0)?)),
+    }
+}
+
+/// Writes network NBT (tag id, payload).
+///
+/// # Errors
+/// Returns an error if a string, array or list is too long, or a list mixes tag types.
+pub fn write_network(tag: &Tag, buf: &mut Vec<u8>) -> Result<()> {
+    buf.push(tag.id());
+    tag.write_payload(buf)
+}
+
+/// Network NBT, which cannot be absent when used as a packet field.
+impl Encode for Tag {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        write_network(self, buf)
+    }
+}
+
+impl Decode for Tag {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        read_network(buf)?.ok_or_else(|| anyhow!("Expected NBT, got TAG_End"))
+    }
+}
+
+/// Converts a tag into its JSON form.
+///
+/// Compounds become objects, lists and arrays become arrays, numbers keep
+/// their value (NaN and infinite floats become `null`). Single-entry
+/// compounds with an empty key inside lists are unwrapped, since that is how
+/// lists of mixed types are encoded.
+impl From<Tag> for Value {
+    fn from(tag: Tag) -> Value {
+        match tag {
+            Tag::Byte(value) => Value::from(value),
+            Tag::Short(value) => Value::from(value),
+            Tag::Int(value) => Value::from(value),
+            Tag::Long(value) => Value::from(value),
+            Tag::Float(value) => Number::from_f64(f64::from(value)).map_or(Value::Null, Value::Number),
+            Tag::Double(value) => Number::from_f64(value).map_or(Value::Null, Value::Number),
+            Tag::ByteArray(values) => Value::from(values),
+            Tag::String(value) => Value::String(value),
+            Tag::List(values) => Value::Array(values.into_iter().map(|tag| Value::from(unwrap_list_element(tag))).collect()),
+            Tag::Compound(entries) => Value::Object(
+                entries.into_iter().map(|(name, value)| (name, Value::from(value))).collect()
+            ),
+            Tag::IntArray(values) => Value::from(values),
+            Tag::LongArray(values) => Value::from(values),
+        }
+    }
+}
+
+fn unwrap_list_element(tag: Tag) -> Tag {
+    match tag {
+        Tag::Compound(mut entries) if entries.len() == 1 && entries.contains_key("") => {
+            entries.remove("").unwrap_or(Tag::Compound(BTreeMap::new()))
+        }
+        tag => tag,
+    }
+}
+
+/// Converts JSON into a tag, e.g. to send a text component as NBT.
+///
+/// Booleans become bytes, integers become ints (or longs if they do not fit),
+/// other numbers become doubles. Arrays of mixed types are written the way
+/// vanilla does, each element wrapped in a compound with an empty key.
+impl TryFrom<Value> for Tag {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Tag> {
+        let tag = match value {
+            Value::Null => return Err(anyhow!("null has no NBT representation")),
+            Value::Bool(value) => Tag::Byte(i8::from(value)),
+            Value::Number(number) => match number.as_i64() {
+                Some(value) => i32::try_from(value).map_or(Tag::Long(value), Tag::Int),
+                None => Tag::Double(number.as_f64().ok_or_else(|| anyhow!("Number {} does not fit in NBT", number))?),
+            },
+            Value::String(value) => Tag::String(value),
+            Value::Array(values) => {
+                let mut tags = values.into_iter().map(Tag::try_from).collect::<Result<Vec<_>>>()?;
+                if tags.windows(2).any(|pair| pair[0].id() != pair[1].id()) {
+                    tags = tags
+                        .into_iter()
+                        .map(|tag| Tag::Compound(BTreeMap::from([(String::new(), tag)])))
+                        .collect();
+                }
+                Tag::List(tags)
+            }
+            Value::Object(entries) => Tag::Compound(
+                entries
+                    .into_iter()
+                    .map(|(name, value)| Ok((name, Tag::try_from(value)?)))
+                    .collect::<Result<_>>()?
+            ),
+        };
+        Ok(tag)
+    }
+}
+
+/// Deserializes a tag without going through JSON.
+///
+/// Numbers keep their NBT type, except that TAG_Byte is also read as `bool`
+/// (nonzero is true) where the target expects one. Enums are read from a
+/// string or a single-key compound, and lists of mixed types are unwrapped
+/// like in the JSON form.
+impl<'de> Deserializer<'de> for Tag {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self {
+            Tag::Byte(value) => visitor.visit_i8(value),
+            Tag::Short(value) => visitor.visit_i16(value),
+            Tag::Int(value) => visitor.visit_i32(value),
+            Tag::Long(value) => visitor.visit_i64(value),
+            Tag::Float(value) => visitor.visit_f32(value),
+            Tag::Double(value) => visitor.visit_f64(value),
+            Tag::ByteArray(values) => SeqDeserializer::new(values.into_iter()).deserialize_any(visitor),
+            Tag::String(value) => visitor.visit_string(value),
+            Tag::List(values) => SeqDeserializer::new(values.into_iter().map(unwrap_list_element)).deserialize_any(visitor),
+            Tag::Compound(entries) => MapDeserializer::new(entries.into_iter()).deserialize_any(visitor),
+            Tag::IntArray(values) => SeqDeserializer::new(values.into_iter()).deserialize_any(visitor),
+            Tag::LongArray(values) => SeqDeserializer::new(values.into_iter()).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self {
+            Tag::Byte(value) => visitor.visit_bool(value != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    /// NBT has no null, so a present tag is always `Some`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(entries) => visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(entries.into_iter()))),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for Tag {
+    type Deserializer = Tag;
+
+    fn into_deserializer(self) -> Tag {
+        self
+    }
+}
+
+/// Deserializes a tag into `T` (see the `Deserializer` impl of [`Tag`]).
+///
+/// # Errors
+/// Returns an error if the tag does not match the shape of `T`.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T> {
+    T::deserialize(tag).context("Failed to deserialize NBT")
+}
@@ -1,8 +1,128 @@
-use std::time::Duration;
-use anyhow::Context;
-use tokio::time::sleep;
-use crate::mc_text::ServerStatus;
-use crate::varint::VarInt;
+use anyhow::{anyhow, Context};
+use crate::framing::encode_frame;
+use crate::mc_text::{BedrockStatus, Description, Players, QueryBasicStat, QueryFullStat, ServerStatus, Version};
+use crate::types::{decode_string, encode_string, Decode, Encode, RemainingBytes, MAX_CHAT_LEN};
+use crate::varint::{VarInt, VarIntRead, VarIntWrite};
+
+/// Protocol version sent in the handshake by default (Minecraft 1.21.2 / 1.21.3).
+pub const DEFAULT_PROTOCOL_VERSION: i32 = 768;
+
+/// Protocol version the vanilla client sends when it does not know the server's
+/// version, e.g. when pinging for the server list.
+pub const UNKNOWN_PROTOCOL_VERSION: i32 = -1;
+
+/// Next state requested by the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Intent {
+    /// Server list ping (next_state = 1).
+    #[default]
+    Status = 1,
+    /// Join the server (next_state = 2).
+    Login = 2,
+    /// Join after a transfer from another server (next_state = 3, 1.20.5+).
+    Transfer = 3,
+}
+
+impl TryFrom<i32> for Intent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        match value {
+            1 => Ok(Intent::Status),
+            2 => Ok(Intent::Login),
+            3 => Ok(Intent::Transfer),
+            _ => Err(anyhow!("Invalid handshake intent {}, expected 1 (status), 2 (login) or 3 (transfer)", value)),
+        }
+    }
+}
+
+/// First protocol version that knows the transfer intent (Minecraft 1.20.5).
+pub const TRANSFER_PROTOCOL_VERSION: i32 = 766;
+
+/// Checks that `protocol_version` can be sent in a handshake.
+///
+/// Any non-negative version is accepted, plus -1 ("unknown").
+pub fn validate_protocol_version(protocol_version: i32) -> anyhow::Result<()> {
+    if protocol_version < UNKNOWN_PROTOCOL_VERSION {
+        return Err(anyhow!("Invalid protocol version {}, expected -1 or a non-negative number", protocol_version));
+    }
+    Ok(())
+}
+
+/// A packet that can be encoded into and decoded from a packet body.
+///
+/// A body is the packet id VarInt followed by the packet fields, i.e. a frame
+/// without its length prefix, as returned by
+/// [`Connection::read_packet`](crate::connection::Connection::read_packet).
+/// Implementors only handle the fields; the packet id is written and checked
+/// by the provided methods.
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::{Packet, PingRequest};
+///
+/// let body = PingRequest::new(42).to_body()?;
+/// assert_eq!(body[0], 0x01);
+///
+/// let ping = PingRequest::from_body(&body)?;
+/// assert_eq!(ping.payload, 42);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait Packet: Sized {
+    /// Packet id within the packet's connection state.
+    const ID: i32;
+
+    /// Writes the packet fields, without the packet id, to `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if a field cannot be represented on the wire.
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()>;
+
+    /// Reads the packet fields, without the packet id, from the front of `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is truncated or malformed.
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<Self>;
+
+    /// Returns the packet body: the packet id followed by the encoded fields.
+    ///
+    /// # Errors
+    /// Returns an error if [`encode`](Self::encode) fails.
+    fn to_body(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.write_var_int(VarInt::from(Self::ID))?;
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Parses a packet from its body.
+    ///
+    /// # Errors
+    /// Returns an error if the packet id is not [`Self::ID`], the fields cannot
+    /// be decoded, or bytes are left over after the last field.
+    fn from_body(body: &[u8]) -> anyhow::Result<Self> {
+        let mut cursor = body;
+
+        let packet_id = i32::from(cursor.read_var_int().context("Failed to read packet id")?);
+        if packet_id != Self::ID {
+            return Err(anyhow!("Unexpected packet id {:#04x}, expected {:#04x}", packet_id, Self::ID));
+        }
+
+        let packet = Self::decode(&mut cursor)?;
+        if !cursor.is_empty() {
+            return Err(anyhow!("Packet {:#04x} has {} unexpected trailing bytes", packet_id, cursor.len()));
+        }
+        Ok(packet)
+    }
+
+    /// Returns the packet as an uncompressed frame, length prefix included.
+    ///
+    /// # Errors
+    /// Returns an error if [`encode`](Self::encode) fails.
+    fn to_frame(&self) -> anyhow::Result<Vec<u8>> {
+        encode_frame(&self.to_body()?, None)
+    }
+}
 
 /// Represents the Minecraft client handshake packet.
 ///
@@ -10,8 +130,11 @@ use crate::varint::VarInt;
 ///
 /// # Example
 /// ```
+/// use mc_ping::packets::ClientHandshake;
+///
 /// let handshake = ClientHandshake::new("127.0.0.1".to_string(), 25565);
-/// let bytes = handshake.to_bytes();
+/// let bytes = handshake.to_bytes()?;
+/// # Ok::<(), anyhow::Error>(())
 /// ```
 #[derive(Debug)]
 pub struct ClientHandshake {
@@ -25,7 +148,7 @@ pub struct ClientHandshake {
     pub server_addr: String,
     /// Server port number.
     pub server_port: u16,
-    /// Next state after handshake: 1 = status, 2 = login.
+    /// Next state after handshake: 1 = status, 2 = login, 3 = transfer.
     pub next_state: VarInt,
 }
 
@@ -33,10 +156,51 @@ impl ClientHandshake {
     /// Creates a new ClientHandshake packet for the given server address and port.
     ///
     /// Automatically calculates packet length and uses default protocol version 768.
+    /// The address is not checked here; an address longer than 255 characters
+    /// makes encoding fail. Use [`with_options`](Self::with_options) to check it up front.
     pub fn new(server_addr: String, server_port: u16) -> ClientHandshake {
-        let packet_id = VarInt::from(0x00);
-        let protocol_version = VarInt::from(768);
-        let next_state = VarInt::from(1);
+        Self::build(server_addr, server_port, DEFAULT_PROTOCOL_VERSION, Intent::Status)
+    }
+
+    /// Creates a new ClientHandshake packet with an explicit protocol version and intent.
+    ///
+    /// # Errors
+    /// Returns an error if `protocol_version` is below -1, `server_addr` is longer
+    /// than the 255 characters the protocol allows, or the transfer intent is used
+    /// with a protocol older than [`TRANSFER_PROTOCOL_VERSION`].
+    ///
+    /// # Example
+    /// ```
+    /// use mc_ping::packets::{ClientHandshake, Intent, UNKNOWN_PROTOCOL_VERSION};
+    ///
+    /// let handshake = ClientHandshake::with_options(
+    ///     "127.0.0.1".to_string(), 25565, UNKNOWN_PROTOCOL_VERSION, Intent::Status,
+    /// )?;
+    /// let bytes = handshake.to_bytes()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_options(
+        server_addr: String,
+        server_port: u16,
+        protocol_version: i32,
+        intent: Intent,
+    ) -> anyhow::Result<ClientHandshake> {
+        validate_protocol_version(protocol_version)?;
+        if intent == Intent::Transfer
+            && protocol_version != UNKNOWN_PROTOCOL_VERSION
+            && protocol_version < TRANSFER_PROTOCOL_VERSION {
+            return Err(anyhow!("Transfer intent needs protocol {} (1.20.5) or newer, got {}", TRANSFER_PROTOCOL_VERSION, protocol_version));
+        }
+        if server_addr.chars().count() > 255 {
+            return Err(anyhow!("Server address is longer than 255 characters"));
+        }
+        Ok(Self::build(server_addr, server_port, protocol_version, intent))
+    }
+
+    fn build(server_addr: String, server_port: u16, protocol_version: i32, intent: Intent) -> ClientHandshake {
+        let packet_id = VarInt::from(Self::ID);
+        let protocol_version = VarInt::from(protocol_version);
+        let next_state = VarInt::from(intent as i32);
 
         // Calculate length of the packet payload:
         // packet_id + protocol_version + length of server_addr string + server_addr bytes + port(2 bytes) + next_state
@@ -50,88 +214,183 @@ impl ClientHandshake {
 
         let len = VarInt::from(len_val as i32);
 
-        let handshake = ClientHandshake {
+        ClientHandshake {
             len,
             packet_id,
             protocol_version,
             server_addr,
             server_port,
             next_state,
-        };
-
-
-        handshake
+        }
     }
 
     /// Serializes the handshake packet into a byte vector ready for sending over the network.
     ///
     /// The format follows Minecraft's VarInt and packet structure conventions.
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut buf = Vec::new();
-
-        // Helper function to write VarInt bytes until continuation bit is zero.
-        fn write_varint_bytes(buf: &mut Vec<u8>, varint_inner: &[u8]) {
-            for &byte in varint_inner {
-                buf.push(byte);
-                if byte & 0b1000_0000 == 0 {
-                    break;
-                }
-            }
-        }
-
-        // Write packet length
-        write_varint_bytes(&mut buf, &self.len.inner);
-        // Write packet ID
-        write_varint_bytes(&mut buf, &self.packet_id.inner);
-        // Write protocol version
-        write_varint_bytes(&mut buf, &self.protocol_version.inner);
+    ///
+    /// # Errors
+    /// Returns an error if the server address is longer than 255 characters.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.to_frame()
+    }
+}
 
-        // Write server address as Minecraft String: VarInt length + UTF-8 bytes
-        let addr_len = VarInt::from(self.server_addr.len() as i32);
-        write_varint_bytes(&mut buf, &addr_len.inner);
-        buf.extend(self.server_addr.as_bytes());
+impl Packet for ClientHandshake {
+    const ID: i32 = 0x00;
 
-        // Write server port as 2 bytes big-endian
-        buf.push((self.server_port >> 8) as u8);
-        buf.push(self.server_port as u8);
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.protocol_version.encode(buf)?;
+        encode_string(&self.server_addr, 255, buf)?;
+        self.server_port.encode(buf)?;
+        self.next_state.encode(buf)
+    }
 
-        // Write next state VarInt
-        write_varint_bytes(&mut buf, &self.next_state.inner);
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<ClientHandshake> {
+        let protocol_version = buf.read_var_int().context("Failed to read protocol version")?;
+        let server_addr = decode_string(buf, 255).context("Failed to read server address")?;
+        let server_port = u16::decode(buf).context("Failed to read server port")?;
+        let next_state = buf.read_var_int().context("Failed to read next state")?;
+        let intent = Intent::try_from(i32::from(next_state))?;
 
-        buf
+        Ok(Self::build(server_addr, server_port, i32::from(protocol_version), intent))
     }
 }
 
 /// Represents the status query packet.
 ///
-/// This packet is sent after handshake to request the server status.
-pub struct StatusQuery {
-    len: VarInt,
-    packet_id: VarInt,
-}
+/// This packet is sent after handshake to request the server status. It has no fields.
+#[derive(Debug, Default)]
+pub struct StatusQuery;
 
 impl StatusQuery {
     /// Creates a new status query packet.
     ///
     /// # Example
     /// ```
+    /// use mc_ping::packets::StatusQuery;
+    ///
     /// let query = StatusQuery::new();
-    /// let bytes = query.to_bytes();
+    /// let bytes = query.to_bytes()?;
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn new() -> StatusQuery {
-        let packet_id = VarInt::from(0x00);
-        let len = VarInt::from(1); // Packet length: 1 byte for packet_id
-        StatusQuery {
-            len,
-            packet_id,
-        }
+        StatusQuery
     }
 
     /// Returns the serialized bytes of the status query packet.
     ///
     /// This packet is always 2 bytes: [0x01, 0x00]
-    pub fn to_bytes(&self) -> Vec<u8> {
-        vec![0x01, 0x00]
+    ///
+    /// # Errors
+    /// Does not fail in practice; the signature matches the other packets.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.to_frame()
+    }
+}
+
+impl Packet for StatusQuery {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, _buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn decode(_buf: &mut &[u8]) -> anyhow::Result<StatusQuery> {
+        Ok(StatusQuery)
+    }
+}
+
+/// Represents the status-state ping request packet.
+///
+/// Sent after the status response; the server echoes the payload back in a
+/// [`PongResponse`], which is used to measure round-trip latency.
+#[derive(Debug)]
+pub struct PingRequest {
+    /// Packet ID (0x01 for ping request).
+    pub packet_id: VarInt,
+    /// Arbitrary value the server must echo back, usually a timestamp.
+    pub payload: i64,
+}
+
+impl PingRequest {
+    /// Creates a new ping request packet with the given payload.
+    ///
+    /// # Example
+    /// ```
+    /// use mc_ping::packets::PingRequest;
+    ///
+    /// let ping = PingRequest::new(42);
+    /// assert_eq!(ping.to_bytes()?.len(), 10);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new(payload: i64) -> PingRequest {
+        PingRequest {
+            packet_id: VarInt::from(Self::ID),
+            payload,
+        }
+    }
+
+    /// Returns the serialized bytes of the ping request packet.
+    ///
+    /// This packet is always 10 bytes: [0x09, 0x01, payload (8 bytes big-endian)]
+    ///
+    /// # Errors
+    /// Does not fail in practice; the signature matches the other packets.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.to_frame()
+    }
+}
+
+impl Packet for PingRequest {
+    const ID: i32 = 0x01;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.payload.encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<PingRequest> {
+        let payload = i64::decode(buf).context("Failed to read ping payload")?;
+        Ok(PingRequest::new(payload))
+    }
+}
+
+/// Represents the server's pong response to a [`PingRequest`].
+#[derive(Debug)]
+pub struct PongResponse {
+    /// Packet ID (should be 0x01).
+    pub packet_id: VarInt,
+    /// Payload echoed back by the server.
+    pub payload: i64,
+}
+
+impl PongResponse {
+    /// Creates a new pong response echoing `payload`.
+    pub fn new(payload: i64) -> PongResponse {
+        PongResponse {
+            packet_id: VarInt::from(Self::ID),
+            payload,
+        }
+    }
+
+    /// Parses a PongResponse from a packet body.
+    ///
+    /// # Errors
+    /// Returns an error if the packet id is not 0x01 or the payload is not 8 bytes.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<PongResponse> {
+        Self::from_body(bytes)
+    }
+}
+
+impl Packet for PongResponse {
+    const ID: i32 = 0x01;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.payload.encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<PongResponse> {
+        let payload = i64::decode(buf).context("Failed to read pong payload")?;
+        Ok(PongResponse::new(payload))
     }
 }
 
@@ -140,7 +399,7 @@ impl StatusQuery {
 /// Contains the raw JSON string with server information.
 #[derive(Debug)]
 pub struct ServerQueryResponse {
-    /// Length of the entire response packet.
+    /// Length of the response packet body (packet id + data).
     pub len: VarInt,
     /// Packet ID (should be 0x00).
     pub packet_id: VarInt,
@@ -151,48 +410,23 @@ pub struct ServerQueryResponse {
 }
 
 impl ServerQueryResponse {
-    /// Parses a ServerQueryResponse from raw bytes.
-    ///
-    /// Reads VarInts for lengths and packet IDs, then extracts the JSON string.
-    ///
-    /// # Panics
-    /// If the byte slice is too short or malformed, this may panic.
-    pub async fn from(bytes: &[u8]) -> ServerQueryResponse {
-        sleep(Duration::from_millis(100)).await; // panic fix
-        // Helper to read a VarInt from a byte slice,
-        fn read_varint(data: &[u8]) -> (VarInt, usize) {
-            let mut val = VarInt::default();
-            let mut i = 0;
-            loop {
-                let byte = data[i];
-                val.inner[i] = byte;
-                i += 1;
-                if byte & 0x80 == 0 {
-                    break;
-                }
-            }
-            (val, i)
-        }
-
-        let mut cursor = 0;
-
-        // 1. Read length VarInt
-        let (len, len_size) = read_varint(&bytes[cursor..]);
-        cursor += len_size;
-
-        // 2. Read packet_id VarInt
-        let (packet_id, packet_id_size) = read_varint(&bytes[cursor..]);
-        cursor += packet_id_size;
-
-        // 3. Read json_len VarInt
-        let (json_len, json_len_size) = read_varint(&bytes[cursor..]);
-        cursor += json_len_size;
-
-        // 4. Read JSON bytes using length from json_len
-        let json_bytes = &bytes[cursor..cursor + i32::from(json_len.clone()) as usize];
-        cursor += i32::from(json_len.clone()) as usize;
-
-        let json = String::from_utf8_lossy(json_bytes).to_string();
+    /// Creates a new status response carrying `json`.
+    ///
+    /// # Example
+    /// ```
+    /// use mc_ping::packets::{Packet, ServerQueryResponse};
+    ///
+    /// let json = r#"{"version":{"name":"1.21","protocol":767},"players":{"max":20,"online":0},"description":"A Minecraft Server"}"#;
+    /// let body = ServerQueryResponse::new(json.to_string()).to_body()?;
+    ///
+    /// let response = ServerQueryResponse::from_body(&body)?;
+    /// assert_eq!(response.json, json);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new(json: String) -> ServerQueryResponse {
+        let packet_id = VarInt::from(Self::ID);
+        let json_len = VarInt::from(json.len() as i32);
+        let len = VarInt::from((packet_id.size() + json_len.size() + json.len()) as i32);
 
         ServerQueryResponse {
             len,
@@ -202,14 +436,35 @@ impl ServerQueryResponse {
         }
     }
 
+    /// Parses a ServerQueryResponse from a packet body.
+    ///
+    /// `bytes` is everything after the length prefix, as returned by
+    /// [`Connection::read_packet`](crate::connection::Connection::read_packet):
+    /// the packet id followed by the length-prefixed JSON string.
+    ///
+    /// # Errors
+    /// Returns an error if the packet id is not 0x00, or if the body is
+    /// truncated or malformed.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<ServerQueryResponse> {
+        Self::from_body(bytes)
+    }
+
     /// Parses the JSON string into a strongly-typed ServerStatus struct.
     ///
     /// Returns an error if JSON deserialization fails.
     ///
     /// # Example
     /// ```
-    /// let response = ServerQueryResponse::from(&bytes);
+    /// use mc_ping::packets::ServerQueryResponse;
+    ///
+    /// let json = r#"{"version":{"name":"1.21","protocol":767},"players":{"max":20,"online":0},"description":"A Minecraft Server"}"#;
+    /// let mut body = vec![0x00, json.len() as u8];
+    /// body.extend_from_slice(json.as_bytes());
+    ///
+    /// let response = ServerQueryResponse::from(&body)?;
     /// let status = response.parse_status()?;
+    /// assert_eq!(status.players.max, 20);
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn parse_status(&self) -> anyhow::Result<ServerStatus> {
         let status: ServerStatus = serde_json::from_str(&self.json)
@@ -217,3 +472,1059 @@ impl ServerQueryResponse {
         Ok(status)
     }
 }
+
+impl Packet for ServerQueryResponse {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        // Status responses with large favicons or mod lists exceed the String limit
+        encode_string(&self.json, usize::MAX, buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<ServerQueryResponse> {
+        let json = Vec::<u8>::decode(buf).context("Failed to read status JSON")?;
+        Ok(ServerQueryResponse::new(String::from_utf8_lossy(&json).into_owned()))
+    }
+}
+
+/// Protocol version sent in the legacy 1.6 ping (74 = Minecraft 1.6.4).
+pub const LEGACY_PROTOCOL_VERSION: u8 = 74;
+
+/// Version name reported for legacy servers that do not send one (Beta 1.8 - 1.3).
+pub const LEGACY_VERSION_NAME: &str = "Legacy";
+
+/// Legacy (pre-Netty) server list ping variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyPingVariant {
+    /// Beta 1.8 - 1.3: a bare `0xFE`, answered with `motd§online§max`.
+    Beta,
+    /// 1.4 - 1.5: `0xFE 0x01`, answered with the `§1` delimited format.
+    V1_4,
+    /// 1.6: `0xFE 0x01` plus an `MC|PingHost` plugin message.
+    V1_6,
+}
+
+/// Encodes a string as UTF-16BE prefixed with its length in characters,
+/// the string format used by pre-Netty (1.6 and older) servers.
+fn write_legacy_string(buf: &mut Vec<u8>, value: &str) {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    buf.extend_from_slice(&(units.len() as u16).to_be_bytes());
+    for unit in units {
+        buf.extend_from_slice(&unit.to_be_bytes());
+    }
+}
+
+/// Represents a legacy server list ping.
+///
+/// Pre-Netty servers do not understand the modern handshake. Depending on the
+/// [`LegacyPingVariant`] this is a bare `0xFE` (Beta 1.8 - 1.3), `0xFE 0x01`
+/// (1.4 - 1.5) or `0xFE 0x01` followed by an `MC|PingHost` plugin message (1.6).
+/// All of them are answered with a `0xFF` kick packet (see [`LegacyPingResponse`]).
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::LegacyPing;
+///
+/// let ping = LegacyPing::new("127.0.0.1".to_string(), 25565);
+/// let bytes = ping.to_bytes();
+/// assert_eq!(&bytes[..3], &[0xFE, 0x01, 0xFA]);
+/// ```
+#[derive(Debug)]
+pub struct LegacyPing {
+    /// Which legacy ping format to send.
+    pub variant: LegacyPingVariant,
+    /// Protocol version byte, 74 for Minecraft 1.6.4.
+    pub protocol_version: u8,
+    /// Server address as a string (domain or IP).
+    pub server_addr: String,
+    /// Server port number.
+    pub server_port: u16,
+}
+
+impl LegacyPing {
+    /// Creates a new legacy 1.6 ping for the given server address and port.
+    pub fn new(server_addr: String, server_port: u16) -> LegacyPing {
+        Self::with_variant(server_addr, server_port, LegacyPingVariant::V1_6)
+    }
+
+    /// Creates a new legacy ping of the given variant.
+    ///
+    /// The address and port are only sent by the 1.6 variant.
+    ///
+    /// # Example
+    /// ```
+    /// use mc_ping::packets::{LegacyPing, LegacyPingVariant};
+    ///
+    /// let ping = LegacyPing::with_variant("127.0.0.1".to_string(), 25565, LegacyPingVariant::Beta);
+    /// assert_eq!(ping.to_bytes(), vec![0xFE]);
+    /// ```
+    pub fn with_variant(server_addr: String, server_port: u16, variant: LegacyPingVariant) -> LegacyPing {
+        LegacyPing {
+            variant,
+            protocol_version: LEGACY_PROTOCOL_VERSION,
+            server_addr,
+            server_port,
+        }
+    }
+
+    /// Serializes the ping into bytes ready for sending over the network.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.variant {
+            LegacyPingVariant::Beta => return vec![0xFE],
+            LegacyPingVariant::V1_4 => return vec![0xFE, 0x01],
+            LegacyPingVariant::V1_6 => {}
+        }
+
+        // 0xFE server list ping, 0x01 payload, 0xFA plugin message
+        let mut buf = vec![0xFE, 0x01, 0xFA];
+        write_legacy_string(&mut buf, "MC|PingHost");
+
+        let mut data = vec![self.protocol_version];
+        write_legacy_string(&mut data, &self.server_addr);
+        data.extend_from_slice(&(self.server_port as i32).to_be_bytes());
+
+        buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        buf.extend(data);
+        buf
+    }
+}
+
+/// Represents the `0xFF` kick packet a legacy server answers a ping with.
+#[derive(Debug)]
+pub struct LegacyPingResponse {
+    /// Protocol version reported by the server.
+    pub protocol_version: i32,
+    /// Server version name, e.g. "1.6.4".
+    pub server_version: String,
+    /// Message of the day.
+    pub motd: String,
+    /// Current number of online players.
+    pub online: i32,
+    /// Maximum number of players allowed on the server.
+    pub max: i32,
+}
+
+impl LegacyPingResponse {
+    /// Parses a LegacyPingResponse from a complete kick packet
+    /// (`0xFF`, length in characters, UTF-16BE string).
+    ///
+    /// 1.4 and newer send `§1\0protocol\0version\0motd\0online\0max`;
+    /// Beta 1.8 - 1.3 send `motd§online§max`, in which case the version name is
+    /// [`LEGACY_VERSION_NAME`] and the protocol version is -1.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is not a kick packet, is truncated, or
+    /// the string does not have the expected fields.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<LegacyPingResponse> {
+        if bytes.first() != Some(&0xFF) {
+            return Err(anyhow!("Expected legacy kick packet (0xFF)"));
+        }
+        if bytes.len() < 3 {
+            return Err(anyhow!("Legacy kick packet is truncated"));
+        }
+
+        let chars = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        let data = &bytes[3..];
+        if data.len() != chars * 2 {
+            return Err(anyhow!("Legacy kick packet length mismatch: expected {} bytes, got {}", chars * 2, data.len()));
+        }
+
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        let text = String::from_utf16(&units).context("Legacy kick packet is not valid UTF-16")?;
+
+        if !text.starts_with("§1\0") {
+            // Beta 1.8 - 1.3; the MOTD itself may contain '§' color codes
+            let fields: Vec<&str> = text.rsplitn(3, '§').collect();
+            if fields.len() != 3 {
+                return Err(anyhow!("Unexpected legacy ping response: {:?}", text));
+            }
+
+            return Ok(LegacyPingResponse {
+                protocol_version: UNKNOWN_PROTOCOL_VERSION,
+                server_version: LEGACY_VERSION_NAME.to_string(),
+                motd: fields[2].to_string(),
+                online: fields[1].parse().context("Invalid online count in legacy ping response")?,
+                max: fields[0].parse().context("Invalid max players in legacy ping response")?,
+            });
+        }
+
+        let fields: Vec<&str> = text.split('\0').collect();
+        if fields.len() != 6 {
+            return Err(anyhow!("Unexpected legacy ping response: {:?}", text));
+        }
+
+        Ok(LegacyPingResponse {
+            protocol_version: fields[1].parse().context("Invalid protocol version in legacy ping response")?,
+            server_version: fields[2].to_string(),
+            motd: fields[3].to_string(),
+            online: fields[4].parse().context("Invalid online count in legacy ping response")?,
+            max: fields[5].parse().context("Invalid max players in legacy ping response")?,
+        })
+    }
+
+    /// Converts the legacy response into the same [`ServerStatus`] shape as a modern ping.
+    ///
+    /// The MOTD becomes a text description; player sample, mods and favicon are empty.
+    pub fn to_status(&self) -> ServerStatus {
+        ServerStatus {
+            version: Version {
+                name: self.server_version.clone(),
+                protocol: self.protocol_version,
+            },
+            description: Description::Text(self.motd.clone()),
+            players: Players {
+                max: self.max,
+                online: self.online,
+                sample: Vec::new(),
+            },
+            mods: Vec::new(),
+            favicon: None,
+            extra: serde_json::Value::Object(Default::default()),
+        }
+    }
+}
+
+/// RakNet "offline message" magic sent in unconnected packets.
+pub const RAKNET_MAGIC: [u8; 16] = [
+    0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE,
+    0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78,
+];
+
+/// Represents the RakNet Unconnected Ping used by Bedrock Edition.
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::UnconnectedPing;
+///
+/// let ping = UnconnectedPing::new(0, 0x1234);
+/// assert_eq!(ping.to_bytes().len(), 33);
+/// ```
+#[derive(Debug)]
+pub struct UnconnectedPing {
+    /// Client time in milliseconds, echoed back in the pong.
+    pub time: i64,
+    /// Random client GUID.
+    pub client_guid: i64,
+}
+
+impl UnconnectedPing {
+    /// Packet ID (0x01 for unconnected ping).
+    pub const PACKET_ID: u8 = 0x01;
+
+    /// Creates a new unconnected ping.
+    pub fn new(time: i64, client_guid: i64) -> UnconnectedPing {
+        UnconnectedPing { time, client_guid }
+    }
+
+    /// Serializes the ping: packet id, time, magic and client GUID.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![Self::PACKET_ID];
+        buf.extend_from_slice(&self.time.to_be_bytes());
+        buf.extend_from_slice(&RAKNET_MAGIC);
+        buf.extend_from_slice(&self.client_guid.to_be_bytes());
+        buf
+    }
+}
+
+/// Represents the RakNet Unconnected Pong a Bedrock server answers with.
+#[derive(Debug)]
+pub struct UnconnectedPong {
+    /// Time echoed from the ping.
+    pub time: i64,
+    /// Server GUID.
+    pub server_guid: i64,
+    /// Raw `MCPE;...` status string.
+    pub data: String,
+}
+
+impl UnconnectedPong {
+    /// Packet ID (0x1C for unconnected pong).
+    pub const PACKET_ID: u8 = 0x1C;
+
+    /// Parses an UnconnectedPong from a datagram.
+    ///
+    /// # Errors
+    /// Returns an error if the datagram is not an unconnected pong, the magic
+    /// does not match, or the packet is truncated.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<UnconnectedPong> {
+        if bytes.first() != Some(&Self::PACKET_ID) {
+            return Err(anyhow!("Expected unconnected pong (0x1c)"));
+        }
+        if bytes.len() < 35 {
+            return Err(anyhow!("Unconnected pong is truncated"));
+        }
+        if bytes[17..33] != RAKNET_MAGIC {
+            return Err(anyhow!("Unconnected pong has invalid magic"));
+        }
+
+        let time = i64::from_be_bytes(bytes[1..9].try_into()?);
+        let server_guid = i64::from_be_bytes(bytes[9..17].try_into()?);
+        let len = u16::from_be_bytes([bytes[33], bytes[34]]) as usize;
+        let data = bytes
+            .get(35..35 + len)
+            .ok_or_else(|| anyhow!("Unconnected pong string is truncated"))?;
+
+        Ok(UnconnectedPong {
+            time,
+            server_guid,
+            data: String::from_utf8_lossy(data).to_string(),
+        })
+    }
+
+    /// Parses the status string into a strongly-typed BedrockStatus struct.
+    ///
+    /// # Errors
+    /// Returns an error if one of the required fields (edition up to max players)
+    /// is missing or not a number.
+    ///
+    /// # Example
+    /// ```
+    /// use mc_ping::packets::{UnconnectedPong, RAKNET_MAGIC};
+    ///
+    /// let data = b"MCPE;Dedicated Server;766;1.21.50;2;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;";
+    /// let mut bytes = vec![0x1C];
+    /// bytes.extend_from_slice(&[0; 16]);
+    /// bytes.extend_from_slice(&RAKNET_MAGIC);
+    /// bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    /// bytes.extend_from_slice(data);
+    ///
+    /// let status = UnconnectedPong::from(&bytes)?.parse_status()?;
+    /// assert_eq!(status.version, "1.21.50");
+    /// assert_eq!(status.port_v4, Some(19132));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn parse_status(&self) -> anyhow::Result<BedrockStatus> {
+        let fields: Vec<&str> = self.data.split(';').collect();
+        if fields.len() < 6 {
+            return Err(anyhow!("Unexpected Bedrock status string: {:?}", self.data));
+        }
+
+        let optional = |i: usize| fields.get(i).filter(|f| !f.is_empty()).map(|f| f.to_string());
+
+        Ok(BedrockStatus {
+            edition: fields[0].to_string(),
+            motd: fields[1].to_string(),
+            protocol: fields[2].parse().context("Invalid protocol version in Bedrock status")?,
+            version: fields[3].to_string(),
+            online: fields[4].parse().context("Invalid online count in Bedrock status")?,
+            max: fields[5].parse().context("Invalid max players in Bedrock status")?,
+            server_id: optional(6),
+            sub_motd: optional(7),
+            game_mode: optional(8),
+            game_mode_id: optional(9).and_then(|f| f.parse().ok()),
+            port_v4: optional(10).and_then(|f| f.parse().ok()),
+            port_v6: optional(11).and_then(|f| f.parse().ok()),
+        })
+    }
+}
+
+/// Magic bytes starting every Query protocol request.
+pub const QUERY_MAGIC: [u8; 2] = [0xFE, 0xFD];
+
+/// Query protocol packet type of the handshake (challenge token) exchange.
+pub const QUERY_TYPE_HANDSHAKE: u8 = 0x09;
+
+/// Query protocol packet type of the basic and full stat exchanges.
+pub const QUERY_TYPE_STAT: u8 = 0x00;
+
+/// Reads a null-terminated string from the front of `cursor`.
+fn read_cstring(cursor: &mut &[u8]) -> anyhow::Result<String> {
+    let end = cursor
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("Unterminated string in query response"))?;
+    let value = String::from_utf8_lossy(&cursor[..end]).to_string();
+    *cursor = &cursor[end + 1..];
+    Ok(value)
+}
+
+/// Represents a GameSpy4 Query protocol request (enabled by `enable-query=true`).
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::QueryRequest;
+///
+/// let request = QueryRequest::handshake(1);
+/// assert_eq!(request.to_bytes(), vec![0xFE, 0xFD, 0x09, 0x00, 0x00, 0x00, 0x01]);
+/// ```
+#[derive(Debug)]
+pub struct QueryRequest {
+    /// Packet type: 0x09 for handshake, 0x00 for stat.
+    pub packet_type: u8,
+    /// Session id, echoed by the server. Only the lower 4 bits of each byte are used.
+    pub session_id: i32,
+    /// Request payload following the session id.
+    pub payload: Vec<u8>,
+}
+
+impl QueryRequest {
+    /// Creates a handshake request asking for a challenge token.
+    pub fn handshake(session_id: i32) -> QueryRequest {
+        QueryRequest {
+            packet_type: QUERY_TYPE_HANDSHAKE,
+            session_id: session_id & 0x0F0F0F0F,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Creates a basic stat request using the challenge token from the handshake.
+    pub fn basic_stat(session_id: i32, challenge_token: i32) -> QueryRequest {
+        QueryRequest {
+            packet_type: QUERY_TYPE_STAT,
+            session_id: session_id & 0x0F0F0F0F,
+            payload: challenge_token.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Creates a full stat request using the challenge token from the handshake.
+    ///
+    /// Identical to a basic stat request padded with four zero bytes.
+    pub fn full_stat(session_id: i32, challenge_token: i32) -> QueryRequest {
+        let mut request = Self::basic_stat(session_id, challenge_token);
+        request.payload.extend_from_slice(&[0x00; 4]);
+        request
+    }
+
+    /// Serializes the request: magic, type, session id and payload.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = QUERY_MAGIC.to_vec();
+        buf.push(self.packet_type);
+        buf.extend_from_slice(&self.session_id.to_be_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+}
+
+/// Represents a GameSpy4 Query protocol response.
+#[derive(Debug)]
+pub struct QueryResponse {
+    /// Packet type: 0x09 for handshake, 0x00 for stat.
+    pub packet_type: u8,
+    /// Session id from the request.
+    pub session_id: i32,
+    /// Response payload following the session id.
+    pub payload: Vec<u8>,
+}
+
+impl QueryResponse {
+    /// Parses a QueryResponse from a datagram.
+    ///
+    /// # Errors
+    /// Returns an error if the datagram is shorter than the 5-byte header.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<QueryResponse> {
+        if bytes.len() < 5 {
+            return Err(anyhow!("Query response is truncated"));
+        }
+
+        Ok(QueryResponse {
+            packet_type: bytes[0],
+            session_id: i32::from_be_bytes(bytes[1..5].try_into()?),
+            payload: bytes[5..].to_vec(),
+        })
+    }
+
+    /// Parses the challenge token from a handshake response.
+    ///
+    /// The server sends it as a null-terminated decimal string.
+    ///
+    /// # Errors
+    /// Returns an error if this is not a handshake response or the token is not a number.
+    pub fn challenge_token(&self) -> anyhow::Result<i32> {
+        if self.packet_type != QUERY_TYPE_HANDSHAKE {
+            return Err(anyhow!("Expected query handshake response, got type {:#04x}", self.packet_type));
+        }
+
+        let token = read_cstring(&mut self.payload.as_slice())?;
+        token.parse().context("Invalid query challenge token")
+    }
+
+    /// Parses a basic stat response.
+    ///
+    /// # Errors
+    /// Returns an error if this is not a stat response or it is truncated or malformed.
+    pub fn parse_basic_stat(&self) -> anyhow::Result<QueryBasicStat> {
+        if self.packet_type != QUERY_TYPE_STAT {
+            return Err(anyhow!("Expected query stat response, got type {:#04x}", self.packet_type));
+        }
+
+        let mut cursor = self.payload.as_slice();
+        let motd = read_cstring(&mut cursor)?;
+        let game_type = read_cstring(&mut cursor)?;
+        let map = read_cstring(&mut cursor)?;
+        let online = read_cstring(&mut cursor)?.parse().context("Invalid online count in query response")?;
+        let max = read_cstring(&mut cursor)?.parse().context("Invalid max players in query response")?;
+
+        // The port is the only little-endian value in the protocol
+        if cursor.len() < 2 {
+            return Err(anyhow!("Query response is truncated"));
+        }
+        let host_port = u16::from_le_bytes([cursor[0], cursor[1]]);
+        cursor = &cursor[2..];
+        let host_ip = read_cstring(&mut cursor)?;
+
+        Ok(QueryBasicStat {
+            motd,
+            game_type,
+            map,
+            online,
+            max,
+            host_port,
+            host_ip,
+        })
+    }
+
+    /// Parses a full stat response.
+    ///
+    /// The payload is 11 bytes of padding, null-terminated key/value pairs ending
+    /// with an empty key, 10 more bytes of padding and the null-terminated player
+    /// names ending with an empty name.
+    ///
+    /// # Errors
+    /// Returns an error if this is not a stat response or it is truncated or malformed.
+    pub fn parse_full_stat(&self) -> anyhow::Result<QueryFullStat> {
+        if self.packet_type != QUERY_TYPE_STAT {
+            return Err(anyhow!("Expected query stat response, got type {:#04x}", self.packet_type));
+        }
+
+        let mut cursor = self.payload
+            .get(11..)
+            .ok_or_else(|| anyhow!("Query response is truncated"))?;
+
+        let mut values = std::collections::HashMap::new();
+        loop {
+            let key = read_cstring(&mut cursor)?;
+            if key.is_empty() {
+                break;
+            }
+            let value = read_cstring(&mut cursor)?;
+            values.insert(key, value);
+        }
+
+        cursor = cursor
+            .get(10..)
+            .ok_or_else(|| anyhow!("Query response is truncated"))?;
+        let mut players = Vec::new();
+        loop {
+            let name = read_cstring(&mut cursor)?;
+            if name.is_empty() {
+                break;
+            }
+            players.push(name);
+        }
+
+        let mut take = |key: &str| values.remove(key).unwrap_or_default();
+
+        // "Paper on 1.21.4: WorldEdit 7.3.9; LuckPerms 5.4.141", or empty on vanilla
+        let plugin_list = take("plugins");
+        let (server_software, plugins) = match plugin_list.split_once(':') {
+            Some((software, list)) => (
+                Some(software.trim().to_string()),
+                list.split(';').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect(),
+            ),
+            None if plugin_list.trim().is_empty() => (None, Vec::new()),
+            None => (Some(plugin_list.trim().to_string()), Vec::new()),
+        };
+
+        Ok(QueryFullStat {
+            motd: take("hostname"),
+            game_type: take("gametype"),
+            game_id: take("game_id"),
+            version: take("version"),
+            server_software,
+            plugins,
+            map: take("map"),
+            online: take("numplayers").parse().context("Invalid online count in query response")?,
+            max: take("maxplayers").parse().context("Invalid max players in query response")?,
+            host_port: take("hostport").parse().context("Invalid host port in query response")?,
+            host_ip: take("hostip"),
+            players,
+            extra: values,
+        })
+    }
+}
+
+/// RCON packet type of a login request.
+pub const RCON_TYPE_LOGIN: i32 = 3;
+
+/// RCON packet type of a command request, also used by the server for auth responses.
+pub const RCON_TYPE_COMMAND: i32 = 2;
+
+/// RCON packet type of a command response.
+pub const RCON_TYPE_RESPONSE: i32 = 0;
+
+/// Represents a Source RCON packet.
+///
+/// On the wire: length (i32 LE, excluding itself), request id (i32 LE),
+/// type (i32 LE), null-terminated body and one more null byte.
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::{RconPacket, RCON_TYPE_COMMAND};
+///
+/// let packet = RconPacket::new(1, RCON_TYPE_COMMAND, "list".to_string());
+/// let bytes = packet.to_bytes();
+/// assert_eq!(RconPacket::from(&bytes[4..])?.body, "list");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket {
+    /// Client chosen request id, echoed by the server (-1 on failed login).
+    pub request_id: i32,
+    /// Packet type, see the `RCON_TYPE_*` constants.
+    pub packet_type: i32,
+    /// Packet body (password, command or command output).
+    pub body: String,
+}
+
+impl RconPacket {
+    /// Creates a new RCON packet.
+    pub fn new(request_id: i32, packet_type: i32, body: String) -> RconPacket {
+        RconPacket {
+            request_id,
+            packet_type,
+            body,
+        }
+    }
+
+    /// Serializes the packet including its length prefix.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = 4 + 4 + self.body.len() + 2;
+        let mut buf = Vec::with_capacity(4 + len);
+        buf.extend_from_slice(&(len as i32).to_le_bytes());
+        buf.extend_from_slice(&self.request_id.to_le_bytes());
+        buf.extend_from_slice(&self.packet_type.to_le_bytes());
+        buf.extend_from_slice(self.body.as_bytes());
+        buf.extend_from_slice(&[0x00, 0x00]);
+        buf
+    }
+
+    /// Parses an RconPacket from everything after the length prefix.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is shorter than the 10-byte minimum.
+    pub fn from(bytes: &[u8]) -> anyhow::Result<RconPacket> {
+        if bytes.len() < 10 {
+            return Err(anyhow!("RCON packet is truncated"));
+        }
+
+        let body = &bytes[8..bytes.len() - 2];
+        Ok(RconPacket {
+            request_id: i32::from_le_bytes(bytes[0..4].try_into()?),
+            packet_type: i32::from_le_bytes(bytes[4..8].try_into()?),
+            body: String::from_utf8_lossy(body).to_string(),
+        })
+    }
+}
+
+/// Represents the Login Start packet (login state, 0x00).
+///
+/// Its layout changed several times; the fields sent are chosen from `protocol_version`.
+///
+/// # Example
+/// ```
+/// use mc_ping::packets::{LoginStart, Packet};
+///
+/// let login = LoginStart::new("Notch".to_string(), 0, 768)?;
+/// let body = login.to_body()?;
+/// assert_eq!(LoginStart::from_body(&body)?.username, "Notch");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct LoginStart {
+    /// Player name, 1 to 16 characters.
+    pub username: String,
+    /// Player UUID, sent from 1.19.1 on.
+    pub uuid: u128,
+    /// Protocol version used to pick the packet layout (-1 = latest).
+    pub protocol_version: i32,
+}
+
+impl LoginStart {
+    /// Creates a new Login Start packet.
+    ///
+    /// # Errors
+    /// Returns an error if `username` is empty or longer than 16 characters.
+    pub fn new(username: String, uuid: u128, protocol_version: i32) -> anyhow::Result<LoginStart> {
+        if username.is_empty() || username.chars().count() > 16 {
+            return Err(anyhow!("Username must be 1 to 16 characters long"));
+        }
+        Ok(LoginStart {
+            username,
+            uuid,
+            protocol_version,
+        })
+    }
+
+    /// Serializes the packet including its length prefix.
+    ///
+    /// # Errors
+    /// Returns an error if the username is longer than 16 characters.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.to_frame()
+    }
+}
+
+/// Decoding reads the latest layout (name and UUID), with `protocol_version` -1.
+impl Packet for LoginStart {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        encode_string(&self.username, 16, buf)?;
+
+        match self.protocol_version {
+            // 1.19: no signature data
+            759 => false.encode(buf),
+            // 1.19.1 - 1.19.2: no signature data, has uuid
+            760 => {
+                false.encode(buf)?;
+                Some(self.uuid).encode(buf)
+            }
+            // 1.19.3 - 1.20.1: has uuid
+            761..=763 => Some(self.uuid).encode(buf),
+            // 1.20.2+ (and unknown): uuid is mandatory
+            v if v >= 764 || v == UNKNOWN_PROTOCOL_VERSION => self.uuid.encode(buf),
+            // before 1.19: name only
+            _ => Ok(()),
+        }
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<LoginStart> {
+        let username = decode_string(buf, 16).context("Failed to read username")?;
+        let uuid = u128::decode(buf).context("Failed to read player UUID")?;
+        LoginStart::new(username, uuid, UNKNOWN_PROTOCOL_VERSION)
+    }
+}
+
+/// Represents a clientbound login-state packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoginResponse {
+    /// Disconnect (0x00) with the reason as a JSON text component.
+    Disconnect {
+        /// Parsed reason; plain strings that are not JSON are kept as a JSON string.
+        reason: serde_json::Value,
+    },
+    /// Encryption Request (0x01), sent by online-mode servers.
+    EncryptionRequest {
+        /// Server id, empty on modern servers.
+        server_id: String,
+        /// Server public key, DER encoded.
+        public_key: Vec<u8>,
+        /// Random token to encrypt with the public key.
+        verify_token: Vec<u8>,
+        /// Whether the client must authenticate with the session server (1.20.5+, else true).
+        should_authenticate: bool,
+    },
+    /// Login Success (0x02).
+    LoginSuccess {
+        /// Player UUID assigned by the server.
+        uuid: u128,
+        /// Player name.
+        username: String,
+    },
+    /// Set Compression (0x03).
+    SetCompression {
+        /// Packets of this size or larger are compressed, negative disables compression.
+        threshold: i32,
+    },
+    /// Login Plugin Request (0x04), used by proxies and mod loaders.
+    PluginRequest {
+        /// Message id to answer with.
+        message_id: i32,
+        /// Plugin channel, e.g. "velocity:player_info".
+        channel: String,
+        /// Channel specific data.
+        data: Vec<u8>,
+    },
+}
+
+impl LoginResponse {
+    /// Parses a login-state packet from a packet body.
+    ///
+    /// Each packet is decoded through its [`Packet`] implementation;
+    /// `protocol_version` selects between layouts that changed over time
+    /// (-1 = latest).
+    ///
+    /// # Errors
+    /// Returns an error for unknown packet ids, truncated packets or trailing bytes.
+    pub fn from(bytes: &[u8], protocol_version: i32) -> anyhow::Result<LoginResponse> {
+        let latest = protocol_version == UNKNOWN_PROTOCOL_VERSION;
+        let mut cursor = bytes;
+        let packet_id = i32::from(cursor.read_var_int().context("Failed to read packet id")?);
+
+        let response = match packet_id {
+            LoginDisconnect::ID => LoginDisconnect::from_body(bytes)?.into(),
+            EncryptionRequest::ID => EncryptionRequest::from_body(bytes)?.into(),
+            LoginSuccess::ID if latest || protocol_version >= 735 => LoginSuccess::from_body(bytes)?.into(),
+            // UUIDs were sent as strings before 1.16
+            LoginSuccess::ID => {
+                let text = String::decode(&mut cursor)?.replace('-', "");
+                let uuid = u128::from_str_radix(&text, 16).context("Invalid UUID in login success packet")?;
+                let username = String::decode(&mut cursor)?;
+                LoginResponse::LoginSuccess { uuid, username }
+            }
+            SetCompression::ID => SetCompression::from_body(bytes)?.into(),
+            LoginPluginRequest::ID => LoginPluginRequest::from_body(bytes)?.into(),
+            _ => return Err(anyhow!("Unexpected packet id {:#04x} in login state", packet_id)),
+        };
+        Ok(response)
+    }
+}
+
+/// Represents the login-state Disconnect packet (0x00).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginDisconnect {
+    /// Parsed reason; plain strings that are not JSON are kept as a JSON string.
+    pub reason: serde_json::Value,
+}
+
+impl Packet for LoginDisconnect {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        encode_string(&self.reason.to_string(), MAX_CHAT_LEN, buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<LoginDisconnect> {
+        let reason = decode_string(buf, MAX_CHAT_LEN).context("Failed to read disconnect reason")?;
+        Ok(LoginDisconnect {
+            reason: serde_json::from_str(&reason).unwrap_or(serde_json::Value::String(reason)),
+        })
+    }
+}
+
+impl From<LoginDisconnect> for LoginResponse {
+    fn from(packet: LoginDisconnect) -> Self {
+        LoginResponse::Disconnect { reason: packet.reason }
+    }
+}
+
+/// Represents the Encryption Request packet (login state, 0x01).
+///
+/// The trailing "should authenticate" flag (1.20.5+) is optional when
+/// decoding and defaults to true.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptionRequest {
+    /// Server id, empty on modern servers.
+    pub server_id: String,
+    /// Server public key, DER encoded.
+    pub public_key: Vec<u8>,
+    /// Random token to encrypt with the public key.
+    pub verify_token: Vec<u8>,
+    /// Whether the client must authenticate with the session server (1.20.5+, else true).
+    pub should_authenticate: bool,
+}
+
+impl Packet for EncryptionRequest {
+    const ID: i32 = 0x01;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        encode_string(&self.server_id, 20, buf)?;
+        self.public_key.encode(buf)?;
+        self.verify_token.encode(buf)?;
+        self.should_authenticate.encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<EncryptionRequest> {
+        let server_id = decode_string(buf, 20).context("Failed to read server id")?;
+        let public_key = Vec::<u8>::decode(buf).context("Failed to read public key")?;
+        let verify_token = Vec::<u8>::decode(buf).context("Failed to read verify token")?;
+        let should_authenticate = if buf.is_empty() { true } else { bool::decode(buf)? };
+        Ok(EncryptionRequest {
+            server_id,
+            public_key,
+            verify_token,
+            should_authenticate,
+        })
+    }
+}
+
+impl From<EncryptionRequest> for LoginResponse {
+    fn from(packet: EncryptionRequest) -> Self {
+        LoginResponse::EncryptionRequest {
+            server_id: packet.server_id,
+            public_key: packet.public_key,
+            verify_token: packet.verify_token,
+            should_authenticate: packet.should_authenticate,
+        }
+    }
+}
+
+/// A signed property of a player profile, e.g. the skin ("textures").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileProperty {
+    /// Property name.
+    pub name: String,
+    /// Property value, base64 encoded.
+    pub value: String,
+    /// Signature of the value by the session server, if signed.
+    pub signature: Option<String>,
+}
+
+impl Encode for ProfileProperty {
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.name.encode(buf)?;
+        self.value.encode(buf)?;
+        self.signature.encode(buf)
+    }
+}
+
+impl Decode for ProfileProperty {
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<ProfileProperty> {
+        Ok(ProfileProperty {
+            name: String::decode(buf)?,
+            value: String::decode(buf)?,
+            signature: Option::<String>::decode(buf)?,
+        })
+    }
+}
+
+/// Represents the Login Success packet (login state, 0x02), 1.16+ layout.
+///
+/// Properties (1.19+) and the "strict error handling" flag (1.20.5 - 1.21.1)
+/// are read if present; encoding writes the 1.21.2+ layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginSuccess {
+    /// Player UUID assigned by the server.
+    pub uuid: u128,
+    /// Player name.
+    pub username: String,
+    /// Profile properties, e.g. the skin.
+    pub properties: Vec<ProfileProperty>,
+}
+
+impl Packet for LoginSuccess {
+    const ID: i32 = 0x02;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.uuid.encode(buf)?;
+        encode_string(&self.username, 16, buf)?;
+        self.properties.encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<LoginSuccess> {
+        let uuid = u128::decode(buf).context("Login success packet is truncated")?;
+        let username = decode_string(buf, 16).context("Failed to read username")?;
+        let properties = if buf.is_empty() { Vec::new() } else { Vec::decode(buf)? };
+        if !buf.is_empty() {
+            bool::decode(buf).context("Failed to read strict error handling flag")?;
+        }
+        Ok(LoginSuccess { uuid, username, properties })
+    }
+}
+
+impl From<LoginSuccess> for LoginResponse {
+    fn from(packet: LoginSuccess) -> Self {
+        LoginResponse::LoginSuccess { uuid: packet.uuid, username: packet.username }
+    }
+}
+
+/// Represents the Set Compression packet (login state, 0x03).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetCompression {
+    /// Packets of this size or larger are compressed, negative disables compression.
+    pub threshold: i32,
+}
+
+impl Packet for SetCompression {
+    const ID: i32 = 0x03;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        VarInt::from(self.threshold).encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<SetCompression> {
+        let threshold = i32::from(VarInt::decode(buf).context("Failed to read compression threshold")?);
+        Ok(SetCompression { threshold })
+    }
+}
+
+impl From<SetCompression> for LoginResponse {
+    fn from(packet: SetCompression) -> Self {
+        LoginResponse::SetCompression { threshold: packet.threshold }
+    }
+}
+
+/// Represents the Login Plugin Request packet (login state, 0x04).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginPluginRequest {
+    /// Message id to answer with.
+    pub message_id: i32,
+    /// Plugin channel, e.g. "velocity:player_info".
+    pub channel: String,
+    /// Channel specific data, running to the end of the packet.
+    pub data: Vec<u8>,
+}
+
+impl Packet for LoginPluginRequest {
+    const ID: i32 = 0x04;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        VarInt::from(self.message_id).encode(buf)?;
+        self.channel.encode(buf)?;
+        RemainingBytes(self.data.clone()).encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<LoginPluginRequest> {
+        let message_id = i32::from(VarInt::decode(buf).context("Failed to read message id")?);
+        let channel = String::decode(buf).context("Failed to read plugin channel")?;
+        let RemainingBytes(data) = RemainingBytes::decode(buf)?;
+        Ok(LoginPluginRequest { message_id, channel, data })
+    }
+}
+
+impl From<LoginPluginRequest> for LoginResponse {
+    fn from(packet: LoginPluginRequest) -> Self {
+        LoginResponse::PluginRequest {
+            message_id: packet.message_id,
+            channel: packet.channel,
+            data: packet.data,
+        }
+    }
+}
+
+/// Represents the Encryption Response packet (login state, 0x01).
+///
+/// Both fields are RSA-encrypted with the public key from the Encryption Request.
+#[derive(Debug)]
+pub struct EncryptionResponse {
+    /// Encrypted shared secret.
+    pub shared_secret: Vec<u8>,
+    /// Encrypted verify token.
+    pub verify_token: Vec<u8>,
+    /// Protocol version used to pick the packet layout (-1 = latest).
+    pub protocol_version: i32,
+}
+
+impl EncryptionResponse {
+    /// Creates a new Encryption Response from the already encrypted values.
+    pub fn new(shared_secret: Vec<u8>, verify_token: Vec<u8>, protocol_version: i32) -> EncryptionResponse {
+        EncryptionResponse {
+            shared_secret,
+            verify_token,
+            protocol_version,
+        }
+    }
+}
+
+/// Decoding reads the latest layout, with `protocol_version` -1.
+impl Packet for EncryptionResponse {
+    const ID: i32 = 0x01;
+
+    fn encode(&self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.shared_secret.encode(buf)?;
+        // 1.19 - 1.19.2: "has verify token" flag, false meant a message signature instead
+        if matches!(self.protocol_version, 759 | 760) {
+            true.encode(buf)?;
+        }
+        self.verify_token.encode(buf)
+    }
+
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<EncryptionResponse> {
+        let shared_secret = Vec::<u8>::decode(buf).context("Failed to read shared secret")?;
+        let verify_token = Vec::<u8>::decode(buf).context("Failed to read verify token")?;
+        Ok(EncryptionResponse::new(shared_secret, verify_token, UNKNOWN_PROTOCOL_VERSION))
+    }
+}
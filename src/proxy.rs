@@ -0,0 +1,178 @@
+//! SOCKS proxy support for [`Connection`](crate::connection::Connection).
+//!
+//! [`Proxy`] selects the protocol: SOCKS4 (IPv4 targets only, host names are
+//! resolved locally), SOCKS4a (the proxy resolves host names) or SOCKS5
+//! (optionally with username/password authentication).
+//!
+//! The connection to the proxy is opened first, so a proxy that cannot be
+//! reached is reported apart from one that refuses the credentials or cannot
+//! reach the server. Those cases are [`ProxyError`]s; use
+//! `err.downcast_ref::<ProxyError>()` to tell them apart.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use anyhow::{anyhow, Result};
+use tokio::net::TcpStream;
+use tokio::time::{timeout_at, Instant};
+use tokio_socks::tcp::{Socks4Stream, Socks5Stream};
+
+/// Longest username, password (RFC 1929) or SOCKS4 user id.
+pub const MAX_CREDENTIAL_LEN: usize = 255;
+
+/// A SOCKS proxy and how to talk to it.
+///
+/// # Example
+///
+/// ```no_run
+/// # use anyhow::Result;
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// use mc_ping::connection::Connection;
+/// use mc_ping::proxy::Proxy;
+///
+/// let proxy = Proxy::Socks4a { addr: ("10.0.0.1".to_string(), 1080), user_id: None };
+/// let mut conn = Connection::new(("play.example.com".to_string(), 25565)).proxy(proxy)?.connect().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proxy {
+    /// SOCKS4, which only carries IPv4 addresses: host names are resolved
+    /// before connecting ("resolve" feature).
+    Socks4 {
+        /// Proxy address as `(host, port)`.
+        addr: (String, u16),
+        /// User id sent in the request, checked by some proxies.
+        user_id: Option<String>,
+    },
+    /// SOCKS4a, which sends host names to the proxy to resolve.
+    Socks4a {
+        /// Proxy address as `(host, port)`.
+        addr: (String, u16),
+        /// User id sent in the request, checked by some proxies.
+        user_id: Option<String>,
+    },
+    /// SOCKS5, which sends host names to the proxy to resolve.
+    Socks5 {
+        /// Proxy address as `(host, port)`.
+        addr: (String, u16),
+        /// `(username, password)` for RFC 1929 authentication.
+        credentials: Option<(String, String)>,
+    },
+}
+
+impl Proxy {
+    /// Returns the proxy address as `(host, port)`.
+    pub fn addr(&self) -> &(String, u16) {
+        match self {
+            Proxy::Socks4 { addr, .. } | Proxy::Socks4a { addr, .. } | Proxy::Socks5 { addr, .. } => addr,
+        }
+    }
+
+    /// Checks the user id or credentials against the protocol limits.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a user id, username or password is empty or longer
+    /// than [`MAX_CREDENTIAL_LEN`] bytes.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Proxy::Socks4 { user_id: Some(user_id), .. } | Proxy::Socks4a { user_id: Some(user_id), .. } => {
+                validate_credential("user id", user_id)
+            }
+            Proxy::Socks5 { credentials: Some((username, password)), .. } => {
+                validate_credential("username", username)?;
+                validate_credential("password", password)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Errors reported while connecting through a proxy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyError {
+    /// The proxy itself could not be reached; holds the reason.
+    Unreachable(String),
+    /// The proxy accepts none of the offered authentication methods, e.g. it
+    /// requires credentials but none were set.
+    AuthenticationRequired,
+    /// The proxy rejected the username and password, or the SOCKS4 user id.
+    AuthenticationFailed,
+    /// The proxy was reached but did not connect to the server; holds the reason.
+    ConnectFailed(String),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::Unreachable(reason) => write!(f, "Proxy is unreachable: {}", reason),
+            ProxyError::AuthenticationRequired => write!(f, "Proxy requires authentication"),
+            ProxyError::AuthenticationFailed => write!(f, "Proxy authentication failed: wrong username or password"),
+            ProxyError::ConnectFailed(reason) => write!(f, "Proxy could not connect to the server: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+/// Connects to `target` through `proxy`.
+///
+/// For [`Proxy::Socks4`] the target host must be an IPv4 address.
+///
+/// # Errors
+///
+/// Returns a [`ProxyError`] if the proxy is unreachable, rejects the
+/// authentication or the connection to `target`, or an error if the target
+/// cannot be sent over SOCKS4 or the whole exchange does not finish before
+/// `deadline`.
+pub(crate) async fn connect_through(proxy: &Proxy, target: (&str, u16), deadline: Instant) -> Result<TcpStream> {
+    if matches!(proxy, Proxy::Socks4 { .. }) && target.0.parse::<Ipv4Addr>().is_err() {
+        return Err(anyhow!("SOCKS4 needs an IPv4 target address, got {}; use SOCKS4a for host names", target.0));
+    }
+
+    let (host, port) = proxy.addr();
+    let socket = match timeout_at(deadline, TcpStream::connect((host.as_str(), *port))).await {
+        Ok(Ok(socket)) => socket,
+        Ok(Err(e)) => return Err(ProxyError::Unreachable(format!("{}:{}: {}", host, port, e)).into()),
+        Err(_) => return Err(ProxyError::Unreachable(format!("{}:{}: timed out", host, port)).into()),
+    };
+
+    let handshake = async {
+        match proxy {
+            Proxy::Socks4 { user_id, .. } | Proxy::Socks4a { user_id, .. } => match user_id {
+                Some(user_id) => Socks4Stream::connect_with_userid_and_socket(socket, target, user_id).await,
+                None => Socks4Stream::connect_with_socket(socket, target).await,
+            }.map(Socks4Stream::into_inner),
+            Proxy::Socks5 { credentials, .. } => match credentials {
+                Some((username, password)) => {
+                    Socks5Stream::connect_with_password_and_socket(socket, target, username, password).await
+                }
+                None => Socks5Stream::connect_with_socket(socket, target).await,
+            }.map(Socks5Stream::into_inner),
+        }
+    };
+    timeout_at(deadline, handshake).await?.map_err(|e| socks_error(e).into())
+}
+
+/// Checks a user id, username or password against the length limits.
+fn validate_credential(name: &str, value: &str) -> Result<()> {
+    if value.is_empty() || value.len() > MAX_CREDENTIAL_LEN {
+        return Err(anyhow!(
+            "Proxy {} must be 1 to {} bytes long, got {}", name, MAX_CREDENTIAL_LEN, value.len()
+        ));
+    }
+    Ok(())
+}
+
+fn socks_error(err: tokio_socks::Error) -> ProxyError {
+    match err {
+        tokio_socks::Error::NoAcceptableAuthMethods | tokio_socks::Error::AuthorizationRequired => {
+            ProxyError::AuthenticationRequired
+        }
+        tokio_socks::Error::PasswordAuthFailure(_)
+        | tokio_socks::Error::IdentdAuthFailure
+        | tokio_socks::Error::InvalidUserIdAuthFailure => ProxyError::AuthenticationFailed,
+        other => ProxyError::ConnectFailed(other.to_string()),
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+use crate::connection::connect_udp;
+#[cfg(feature = "resolve")]
+use std::sync::Arc;
+#[cfg(feature = "resolve")]
+use crate::resolver::{Resolver, SystemResolver};
+use crate::mc_text::{QueryBasicStat, QueryFullStat};
+use crate::packets::{QueryRequest, QueryResponse};
+
+/// Largest datagram accepted from the server. Full stat responses with many
+/// players and plugins can exceed the usual MTU.
+const MAX_DATAGRAM_LEN: usize = 65_535;
+
+/// Represents a client for the GameSpy4 Query protocol over UDP.
+///
+/// Servers with `enable-query=true` in `server.properties` answer on
+/// `query.port` (the game port by default) with the full player list, plugin
+/// list, map name and server software, which the status ping does not expose.
+///
+/// # Fields
+///
+/// * `timeout`: Optional timeout duration in milliseconds for resolving the address and for each exchange.
+/// * `addr`: Target query address `(host, port)`.
+/// * `session_id`: Session id sent with every request, random by default.
+/// * `resolver`: Resolves host names, the system resolver unless set ("resolve" feature).
+pub struct QueryConnection {
+    pub is_initialized: bool,
+    pub timeout: Option<u64>,
+    pub addr: (String, u16),
+    pub session_id: i32,
+    #[cfg(feature = "resolve")]
+    pub resolver: Arc<dyn Resolver>,
+}
+
+impl QueryConnection {
+    /// Creates a new `QueryConnection` with the target query address.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mc_ping::query::QueryConnection;
+    ///
+    /// let conn = QueryConnection::new(("127.0.0.1".to_string(), 25565));
+    /// ```
+    pub fn new(addr: (String, u16)) -> Self {
+        Self {
+            is_initialized: true,
+            timeout: None,
+            addr,
+            session_id: RandomState::new().build_hasher().finish() as i32 & 0x0F0F0F0F,
+            #[cfg(feature = "resolve")]
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+
+    /// Sets the timeout for resolving the address and for each query exchange (milliseconds).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use mc_ping::query::QueryConnection;
+    ///
+    /// let conn = QueryConnection::new(("127.0.0.1".to_string(), 25565)).timeout(5000)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timeout(mut self, timeout: u64) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: QueryConnection::new((addr, port)).timeout(u64)"));
+        }
+
+        self.timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// Sets the resolver used for host names, e.g. one shared with other connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called before initialization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// use std::sync::Arc;
+    /// use mc_ping::query::QueryConnection;
+    /// use mc_ping::resolver::{CachingResolver, SystemResolver};
+    ///
+    /// let resolver = Arc::new(CachingResolver::new(SystemResolver));
+    /// let conn = QueryConnection::new(("example.com".to_string(), 25565)).resolver(resolver)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "resolve")]
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Result<Self> {
+        if !self.is_initialized {
+            return Err(anyhow!("using: QueryConnection::new((addr, port)).resolver(Arc<dyn Resolver>)"));
+        }
+
+        self.resolver = resolver;
+        Ok(self)
+    }
+
+    /// Internal helper to send a request and wait for the response to this session.
+    ///
+    /// Datagrams for other sessions are ignored until the timeout expires.
+    async fn __exchange(&self, socket: &UdpSocket, request: QueryRequest) -> Result<QueryResponse> {
+        let _timeout = self.timeout.unwrap_or(8000);
+
+        timeout(Duration::from_millis(_timeout), async {
+            socket.send(&request.to_bytes()).await?;
+
+            let mut buf = vec![0u8; MAX_DATAGRAM_LEN];
+            loop {
+                let n = socket.recv(&mut buf).await?;
+                match QueryResponse::from(&buf[..n]) {
+                    Ok(response) if response.session_id == request.session_id
+                        && response.packet_type == request.packet_type => return Ok(response),
+                    _ => continue,
+                }
+            }
+        }).await?
+    }
+
+    /// Internal helper to open a socket and obtain a challenge token.
+    async fn __handshake(&self) -> Result<(UdpSocket, i32)> {
+        let _timeout = self.timeout.unwrap_or(8000);
+
+        // Resolving gets the same timeout as each exchange
+        let connect = async {
+            #[cfg(feature = "resolve")]
+            let socket = connect_udp(&self.addr, &*self.resolver).await?;
+            #[cfg(not(feature = "resolve"))]
+            let socket = connect_udp(&self.addr).await?;
+            Ok::<_, anyhow::Error>(socket)
+        };
+        let socket = timeout(Duration::from_millis(_timeout), connect).await??;
+        let response = self.__exchange(&socket, QueryRequest::handshake(self.session_id)).await?;
+        let token = response.challenge_token()?;
+        Ok((socket, token))
+    }
+
+    /// Performs the handshake and a basic stat request.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the server does not answer (query disabled), times out,
+    /// or the response is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::query::QueryConnection;
+    ///
+    /// let conn = QueryConnection::new(("127.0.0.1".to_string(), 25565)).timeout(5000)?;
+    /// let stat = conn.basic_stat().await?;
+    /// println!("{} on {}", stat.motd, stat.map);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn basic_stat(&self) -> Result<QueryBasicStat> {
+        let (socket, token) = self.__handshake().await?;
+        let response = self.__exchange(&socket, QueryRequest::basic_stat(self.session_id, token)).await?;
+        response.parse_basic_stat()
+    }
+
+    /// Performs the handshake and a full stat request.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the server does not answer (query disabled), times out,
+    /// or the response is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::query::QueryConnection;
+    ///
+    /// let conn = QueryConnection::new(("127.0.0.1".to_string(), 25565)).timeout(5000)?;
+    /// let stat = conn.full_stat().await?;
+    /// println!("{:?}: {:?}", stat.server_software, stat.players);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn full_stat(&self) -> Result<QueryFullStat> {
+        let (socket, token) = self.__handshake().await?;
+        let response = self.__exchange(&socket, QueryRequest::full_stat(self.session_id, token)).await?;
+        response.parse_full_stat()
+    }
+}
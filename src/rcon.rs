@@ -0,0 +1,230 @@
+use std::fmt;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use crate::connection::Connection;
+use crate::packets::{RconPacket, RCON_TYPE_COMMAND, RCON_TYPE_LOGIN, RCON_TYPE_RESPONSE};
+
+/// Longest command the Minecraft server accepts in one packet.
+pub const MAX_COMMAND_LEN: usize = 1446;
+
+/// Largest packet accepted from the server. Servers split responses into
+/// chunks of 4096 characters, or of 4096 bytes decoded again with U+FFFD in
+/// place of cut characters; as UTF-8 a chunk takes up to 3 bytes per character.
+const MAX_RCON_PACKET_LEN: usize = 3 * 4096 + 10;
+
+/// Errors specific to RCON, returned inside [`anyhow::Error`].
+///
+/// Use `err.downcast_ref::<RconError>()` to tell them apart from I/O errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RconError {
+    /// The server rejected the password.
+    AuthenticationFailed,
+    /// A command was sent before a successful [`RconConnection::authenticate`].
+    NotAuthenticated,
+    /// The command is longer than [`MAX_COMMAND_LEN`] bytes.
+    CommandTooLong(usize),
+    /// The server sent a packet that does not belong to the current exchange.
+    UnexpectedPacket(RconPacket),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RconError::AuthenticationFailed => write!(f, "RCON authentication failed: wrong password"),
+            RconError::NotAuthenticated => write!(f, "RCON connection is not authenticated, call .authenticate() first"),
+            RconError::CommandTooLong(len) => write!(f, "RCON command is {} bytes long, limit is {}", len, MAX_COMMAND_LEN),
+            RconError::UnexpectedPacket(packet) => write!(f, "Unexpected RCON packet: {:?}", packet),
+        }
+    }
+}
+
+impl std::error::Error for RconError {}
+
+/// Represents an RCON (Source RCON protocol) session.
+///
+/// Built on top of a connected [`Connection`], so the timeout and SOCKS5 proxy
+/// settings of the connection are used for RCON as well.
+///
+/// # Fields
+///
+/// * `connection`: The underlying connection, connected to the RCON port.
+/// * `is_authenticated`: Whether [`authenticate`](RconConnection::authenticate) succeeded.
+pub struct RconConnection {
+    pub connection: Connection<TcpStream>,
+    pub is_authenticated: bool,
+    next_id: i32,
+}
+
+impl RconConnection {
+    /// Creates a new RCON session over an already connected `Connection`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::rcon::RconConnection;
+    ///
+    /// let mut conn = Connection::new(("127.0.0.1".to_string(), 25575));
+    /// conn = conn.timeout(5000)?.connect().await?;
+    /// let mut rcon = RconConnection::new(conn);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(connection: Connection<TcpStream>) -> Self {
+        Self {
+            connection,
+            is_authenticated: false,
+            next_id: 1,
+        }
+    }
+
+    /// Internal helper to hand out request ids, skipping -1 (auth failure marker).
+    fn __next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1).filter(|id| *id > 0).unwrap_or(1);
+        id
+    }
+
+    /// Internal helper to send a single packet.
+    async fn __send_packet(&mut self, packet: RconPacket) -> Result<()> {
+        let _timeout = self.connection.timeout.unwrap_or(9000);
+
+        let stream = match &mut self.connection.stream {
+            Some(s) => s,
+            None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect()?")),
+        };
+
+        timeout(Duration::from_millis(_timeout), stream.write_all(&packet.to_bytes())).await??;
+        Ok(())
+    }
+
+    /// Internal helper to read a single packet.
+    async fn __read_packet(&mut self) -> Result<RconPacket> {
+        let _timeout = self.connection.timeout.unwrap_or(9000);
+
+        let stream = match &mut self.connection.stream {
+            Some(s) => s,
+            None => return Err(anyhow!("TCPstream is None. Maybe you forgot to .connect()?")),
+        };
+
+        timeout(Duration::from_millis(_timeout), async {
+            let len = stream.read_i32_le().await?;
+            if len < 10 || len as usize > MAX_RCON_PACKET_LEN {
+                return Err(anyhow!("Invalid RCON packet length: {}", len));
+            }
+
+            let mut buf = vec![0u8; len as usize];
+            stream.read_exact(&mut buf).await?;
+            RconPacket::from(&buf)
+        }).await?
+    }
+
+    /// Logs in with the RCON password (`rcon.password` in `server.properties`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RconError::AuthenticationFailed`] if the password is wrong, or
+    /// an error if the stream is not connected or I/O fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::rcon::{RconConnection, RconError};
+    ///
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25575)).connect().await?;
+    /// let mut rcon = RconConnection::new(conn);
+    /// match rcon.authenticate("hunter2").await {
+    ///     Err(e) if e.downcast_ref::<RconError>() == Some(&RconError::AuthenticationFailed) => {
+    ///         println!("wrong password");
+    ///     }
+    ///     other => other?,
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn authenticate(&mut self, password: &str) -> Result<()> {
+        let id = self.__next_id();
+        self.__send_packet(RconPacket::new(id, RCON_TYPE_LOGIN, password.to_string())).await?;
+
+        loop {
+            let packet = self.__read_packet().await?;
+            if packet.request_id == -1 {
+                self.is_authenticated = false;
+                return Err(RconError::AuthenticationFailed.into());
+            }
+            if packet.request_id != id {
+                return Err(RconError::UnexpectedPacket(packet).into());
+            }
+            // Source servers send an empty RESPONSE_VALUE before the auth response
+            if packet.packet_type == RCON_TYPE_COMMAND {
+                self.is_authenticated = true;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes a command and returns its full output.
+    ///
+    /// Responses longer than 4096 bytes are split by the server into several
+    /// packets. To know when the last one arrived, an empty `RESPONSE_VALUE`
+    /// packet is sent right after the command; the server answers it only after
+    /// the whole command response, so everything before that answer is
+    /// concatenated.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RconError::NotAuthenticated`] before a successful login,
+    /// [`RconError::CommandTooLong`] for oversized commands, or an error if I/O fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use anyhow::Result;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// use mc_ping::connection::Connection;
+    /// use mc_ping::rcon::RconConnection;
+    ///
+    /// let conn = Connection::new(("127.0.0.1".to_string(), 25575)).connect().await?;
+    /// let mut rcon = RconConnection::new(conn);
+    /// rcon.authenticate("hunter2").await?;
+    /// println!("{}", rcon.command("list").await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn command(&mut self, command: &str) -> Result<String> {
+        if !self.is_authenticated {
+            return Err(RconError::NotAuthenticated.into());
+        }
+        if command.len() > MAX_COMMAND_LEN {
+            return Err(RconError::CommandTooLong(command.len()).into());
+        }
+
+        let id = self.__next_id();
+        let sentinel_id = self.__next_id();
+        self.__send_packet(RconPacket::new(id, RCON_TYPE_COMMAND, command.to_string())).await?;
+        self.__send_packet(RconPacket::new(sentinel_id, RCON_TYPE_RESPONSE, String::new())).await?;
+
+        let mut output = String::new();
+        loop {
+            let packet = self.__read_packet().await?;
+            if packet.request_id == sentinel_id {
+                return Ok(output);
+            }
+            if packet.request_id != id {
+                return Err(RconError::UnexpectedPacket(packet).into());
+            }
+            output.push_str(&packet.body);
+        }
+    }
+}
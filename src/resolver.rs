@@ -0,0 +1,317 @@
+//! Pluggable host name resolution ("resolve" feature).
+//!
+//! [`Connection`](crate::connection::Connection),
+//! [`BedrockConnection`](crate::bedrock::BedrockConnection) and
+//! [`QueryConnection`](crate::query::QueryConnection) resolve host names, and
+//! look up `_minecraft._tcp` SRV records, through an `Arc<dyn Resolver>`,
+//! [`SystemResolver`] by default. Resolvers compose: [`CachingResolver`]
+//! keeps answers of another resolver for their TTL, and [`StaticResolver`]
+//! answers configured hosts itself (like `/etc/hosts`, without SRV records)
+//! and hands everything else to another resolver.
+//!
+//! Sharing one `Arc` between many connections shares the cache, which is what
+//! makes it worthwhile when pinging many servers.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use anyhow::Result;
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! use std::sync::Arc;
+//! use mc_ping::connection::Connection;
+//! use mc_ping::resolver::{CachingResolver, StaticResolver, SystemResolver};
+//!
+//! let resolver = Arc::new(
+//!     StaticResolver::new(CachingResolver::new(SystemResolver))
+//!         .host("staging.example.com", "10.0.0.5".parse()?)
+//! );
+//! for host in ["staging.example.com", "play.example.com"] {
+//!     let mut conn = Connection::new((host.to_string(), 25565)).resolver(resolver.clone())?.connect().await?;
+//!     println!("{:?}", conn.get_status().await?.description);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use tokio::net::lookup_host;
+use crate::dns::{cached_system_nameserver, query_srv, SrvRecord, MINECRAFT_SRV_PREFIX};
+
+/// Future returned by [`Resolver::resolve`].
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<Lookup>> + Send + 'a>>;
+
+/// Future returned by [`Resolver::resolve_srv`].
+pub type SrvFuture<'a> = Pin<Box<dyn Future<Output = Result<SrvLookup>> + Send + 'a>>;
+
+/// TTL used by [`CachingResolver`] for answers that carry none.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Number of cached hosts above which expired entries are dropped.
+const PURGE_THRESHOLD: usize = 1024;
+
+/// Addresses a host name resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup {
+    /// Addresses in the order they should be tried.
+    pub addrs: Vec<IpAddr>,
+    /// How long the answer may be cached, if the resolver knows.
+    pub ttl: Option<Duration>,
+}
+
+/// `_minecraft._tcp` SRV records of a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvLookup {
+    /// Records in no particular order; empty if the host has none.
+    pub records: Vec<SrvRecord>,
+    /// How long the answer may be cached, if the resolver knows.
+    pub ttl: Option<Duration>,
+}
+
+/// Resolves host names to IP addresses.
+///
+/// Implementations must be shareable between tasks; the futures are boxed so
+/// the trait can be used as `dyn Resolver`.
+pub trait Resolver: Send + Sync {
+    /// Resolves `host`, a domain name without port.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the name cannot be resolved.
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a>;
+
+    /// Looks up the `_minecraft._tcp` SRV records of `host`.
+    ///
+    /// `nameserver` is the one set with
+    /// [`Connection::nameserver`](crate::connection::Connection::nameserver),
+    /// if any. The default implementation queries it, or the first nameserver
+    /// of `/etc/resolv.conf`, directly over UDP. It waits for the answer until
+    /// dropped, so callers put a timeout around it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if there is no nameserver, or the query fails.
+    fn resolve_srv<'a>(&'a self, host: &'a str, nameserver: Option<SocketAddr>) -> SrvFuture<'a> {
+        Box::pin(async move {
+            let nameserver = match nameserver {
+                Some(nameserver) => nameserver,
+                None => cached_system_nameserver().await.ok_or_else(|| anyhow!("No nameserver in /etc/resolv.conf"))?,
+            };
+            let name = format!("{}.{}", MINECRAFT_SRV_PREFIX, host.trim_end_matches('.'));
+            let (records, ttl) = query_srv(&name, nameserver).await?;
+            Ok(SrvLookup { records, ttl })
+        })
+    }
+}
+
+impl<R: Resolver + ?Sized> Resolver for Arc<R> {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        (**self).resolve(host)
+    }
+
+    fn resolve_srv<'a>(&'a self, host: &'a str, nameserver: Option<SocketAddr>) -> SrvFuture<'a> {
+        (**self).resolve_srv(host, nameserver)
+    }
+}
+
+/// Resolves through the operating system (`getaddrinfo`), which reports no TTL.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let addrs: Vec<IpAddr> = lookup_host((host, 0)).await?.map(|addr| addr.ip()).collect();
+            if addrs.is_empty() {
+                return Err(anyhow!("Could not resolve address: {}", host));
+            }
+            Ok(Lookup { addrs, ttl: None })
+        })
+    }
+}
+
+/// Map of values that expire, shared between tasks.
+struct TtlCache<K, V> {
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    fn new() -> Self {
+        Self { entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the value under `key` and its remaining TTL, unless it expired.
+    fn get(&self, key: &K) -> Option<(V, Duration)> {
+        let now = Instant::now();
+        match self.__entries().get(key) {
+            Some((expires, value)) if *expires > now => Some((value.clone(), *expires - now)),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, key: K, value: V, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.__entries();
+        if entries.len() >= PURGE_THRESHOLD {
+            entries.retain(|_, (expires, _)| *expires > now);
+        }
+        entries.insert(key, (now + ttl, value));
+    }
+
+    fn clear(&self) {
+        self.__entries().clear();
+    }
+
+    /// Internal helper to lock the map, which stays usable if a holder panicked.
+    fn __entries(&self) -> std::sync::MutexGuard<'_, HashMap<K, (Instant, V)>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Caches the answers of another resolver until their TTL runs out.
+///
+/// Answers without a TTL are kept for [`DEFAULT_TTL`], or the duration set
+/// with [`CachingResolver::default_ttl`]. SRV answers are cached too, including
+/// the common answer that a host has no SRV records. Failures are not cached.
+pub struct CachingResolver<R> {
+    inner: R,
+    default_ttl: Duration,
+    cache: TtlCache<String, Vec<IpAddr>>,
+    srv_cache: TtlCache<(String, Option<SocketAddr>), Vec<SrvRecord>>,
+}
+
+impl<R: Resolver> CachingResolver<R> {
+    /// Creates an empty cache in front of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            default_ttl: DEFAULT_TTL,
+            cache: TtlCache::new(),
+            srv_cache: TtlCache::new(),
+        }
+    }
+
+    /// Sets how long answers without a TTL are cached.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Drops every cached answer.
+    pub fn clear(&self) {
+        self.cache.clear();
+        self.srv_cache.clear();
+    }
+}
+
+impl<R: Resolver> Resolver for CachingResolver<R> {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let key = normalize_host(host);
+            if let Some((addrs, ttl)) = self.cache.get(&key) {
+                return Ok(Lookup { addrs, ttl: Some(ttl) });
+            }
+
+            let lookup = self.inner.resolve(host).await?;
+            let ttl = lookup.ttl.unwrap_or(self.default_ttl);
+            self.cache.insert(key, lookup.addrs.clone(), ttl);
+            Ok(Lookup { addrs: lookup.addrs, ttl: Some(ttl) })
+        })
+    }
+
+    fn resolve_srv<'a>(&'a self, host: &'a str, nameserver: Option<SocketAddr>) -> SrvFuture<'a> {
+        Box::pin(async move {
+            let key = (normalize_host(host), nameserver);
+            if let Some((records, ttl)) = self.srv_cache.get(&key) {
+                return Ok(SrvLookup { records, ttl: Some(ttl) });
+            }
+
+            let lookup = self.inner.resolve_srv(host, nameserver).await?;
+            let ttl = lookup.ttl.unwrap_or(self.default_ttl);
+            self.srv_cache.insert(key, lookup.records.clone(), ttl);
+            Ok(SrvLookup { records: lookup.records, ttl: Some(ttl) })
+        })
+    }
+}
+
+/// Answers configured hosts from a fixed map, like `/etc/hosts`, and passes
+/// every other name to another resolver.
+///
+/// Configured hosts have no SRV records, so a host overridden here is
+/// connected to as given even on port 25565, never to a real SRV target.
+pub struct StaticResolver<R> {
+    fallback: R,
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl<R: Resolver> StaticResolver<R> {
+    /// Creates an empty override map in front of `fallback`.
+    pub fn new(fallback: R) -> Self {
+        Self {
+            fallback,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Adds `ip` to the addresses of `host`. Names are case-insensitive.
+    pub fn host(mut self, host: &str, ip: IpAddr) -> Self {
+        self.hosts.entry(normalize_host(host)).or_default().push(ip);
+        self
+    }
+
+    /// Adds the entries of a hosts file: an IP address followed by names per
+    /// line, `#` starting a comment. Lines without a valid address are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mc_ping::resolver::{StaticResolver, SystemResolver};
+    ///
+    /// let resolver = StaticResolver::new(SystemResolver)
+    ///     .hosts("10.0.0.5 staging.example.com lobby.staging.example.com # staging\n");
+    /// ```
+    pub fn hosts(mut self, contents: &str) -> Self {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(Ok(ip)) = fields.next().map(str::parse::<IpAddr>) else {
+                continue;
+            };
+            for host in fields {
+                self = self.host(host, ip);
+            }
+        }
+        self
+    }
+}
+
+impl<R: Resolver> Resolver for StaticResolver<R> {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        match self.hosts.get(&normalize_host(host)) {
+            Some(addrs) => {
+                let lookup = Lookup { addrs: addrs.clone(), ttl: None };
+                Box::pin(async move { Ok(lookup) })
+            }
+            None => self.fallback.resolve(host),
+        }
+    }
+
+    fn resolve_srv<'a>(&'a self, host: &'a str, nameserver: Option<SocketAddr>) -> SrvFuture<'a> {
+        if self.hosts.contains_key(&normalize_host(host)) {
+            return Box::pin(async { Ok(SrvLookup { records: Vec::new(), ttl: None }) });
+        }
+        self.fallback.resolve_srv(host, nameserver)
+    }
+}
+
+/// Lowercases a host name and drops the trailing dot of a fully qualified name.
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
@@ -0,0 +1,414 @@
+//! Codec for the protocol's field types.
+//!
+//! Packets are sequences of these types, so a packet's
+//! [`encode`](crate::packets::Packet::encode) and
+//! [`decode`](crate::packets::Packet::decode) can be written as one call per
+//! field instead of hand-serializing bytes:
+//!
+//! | Protocol type        | Rust type                          |
+//! |----------------------|------------------------------------|
+//! | Boolean              | `bool`                             |
+//! | Byte, Short, Int ... | `i8`, `u8`, `i16`, `u16`, `i32`, `i64`, `f32`, `f64` (big-endian) |
+//! | VarInt, VarLong      | [`VarInt`], [`VarLong`]            |
+//! | String (32767)       | `String`                           |
+//! | Identifier           | [`Identifier`]                     |
+//! | UUID                 | `u128`                             |
+//! | Position             | [`Position`]                       |
+//! | Optional X           | `Option<X>`, prefixed by a Boolean |
+//! | Prefixed Array of X  | `Vec<X>`, prefixed by a VarInt length; `Vec<u8>` is a prefixed byte array |
+//! | Byte Array (rest)    | [`RemainingBytes`]                 |
+//!
+//! # Example
+//! ```
+//! use mc_ping::types::{Decode, Encode, Identifier};
+//!
+//! let mut buf = Vec::new();
+//! "Notch".encode(&mut buf)?;
+//! Some(Identifier::new("minecraft", "brand")?).encode(&mut buf)?;
+//! vec![1u16, 2, 3].encode(&mut buf)?;
+//!
+//! let mut cursor = &buf[..];
+//! assert_eq!(String::decode(&mut cursor)?, "Notch");
+//! assert_eq!(Option::<Identifier>::decode(&mut cursor)?.unwrap().to_string(), "minecraft:brand");
+//! assert_eq!(Vec::<u16>::decode(&mut cursor)?, vec![1, 2, 3]);
+//! assert!(cursor.is_empty());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use crate::varint::{VarInt, VarLong};
+
+/// Default length limit of a String, in UTF-16 code units as counted by the vanilla server.
+pub const MAX_STRING_LEN: usize = 32767;
+
+/// Length limit of a JSON text component (chat, disconnect reasons), in UTF-16 code units.
+pub const MAX_CHAT_LEN: usize = 262_144;
+
+/// Namespace used by identifiers written without one.
+pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// A value that can be written to a packet.
+pub trait Encode {
+    /// Appends the encoded value to `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if the value cannot be represented on the wire, e.g. a
+    /// string over its length limit.
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()>;
+}
+
+/// A value that can be read from a packet.
+pub trait Decode: Sized {
+    /// Reads a value from the front of `buf` and advances it past the value.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is truncated or the value is malformed.
+    fn decode(buf: &mut &[u8]) -> Result<Self>;
+}
+
+/// Splits `len` bytes off the front of `buf`.
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        return Err(anyhow!("Expected {} more bytes, got {}", len, buf.len()));
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+/// Reads a VarInt length prefix and checks it against the bytes left in `buf`.
+///
+/// Every encoded element takes at least one byte, so a larger length can only
+/// come from a malformed packet and is rejected before anything is allocated.
+fn read_len(buf: &mut &[u8]) -> Result<usize> {
+    let len = i32::from(VarInt::decode(buf)?);
+    if len < 0 || len as usize > buf.len() {
+        return Err(anyhow!("Length {} exceeds remaining packet size {}", len, buf.len()));
+    }
+    Ok(len as usize)
+}
+
+macro_rules! number_impl {
+    ($($number: ty),*) => {
+        $(
+        impl Encode for $number {
+            fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+                buf.extend_from_slice(&self.to_be_bytes());
+                Ok(())
+            }
+        }
+
+        impl Decode for $number {
+            fn decode(buf: &mut &[u8]) -> Result<Self> {
+                let bytes = take(buf, size_of::<$number>())?;
+                // `take` returned exactly the size of the number
+                Ok(<$number>::from_be_bytes(bytes.try_into()?))
+            }
+        }
+        )*
+    };
+}
+
+number_impl!(i8, u8, i16, u16, i32, i64, f32, f64, u128);
+
+impl Encode for bool {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.push(u8::from(*self));
+        Ok(())
+    }
+}
+
+impl Decode for bool {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match u8::decode(buf)? {
+            0x00 => Ok(false),
+            0x01 => Ok(true),
+            byte => Err(anyhow!("Invalid boolean {:#04x}", byte)),
+        }
+    }
+}
+
+impl Encode for VarInt {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+}
+
+impl Decode for VarInt {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let (value, len) = VarInt::decode_slice(buf)?;
+        *buf = &buf[len..];
+        Ok(value)
+    }
+}
+
+impl Encode for VarLong {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+}
+
+impl Decode for VarLong {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let (value, len) = VarLong::decode_slice(buf)?;
+        *buf = &buf[len..];
+        Ok(value)
+    }
+}
+
+/// Writes a String limited to `max_len` UTF-16 code units.
+///
+/// # Errors
+/// Returns an error if `value` is longer than `max_len`.
+pub fn encode_string(value: &str, max_len: usize, buf: &mut Vec<u8>) -> Result<()> {
+    let len = value.encode_utf16().count();
+    if len > max_len {
+        return Err(anyhow!("String is {} characters long, the limit is {}", len, max_len));
+    }
+    VarInt::from(value.len() as i32).encode(buf)?;
+    buf.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// Reads a String limited to `max_len` UTF-16 code units.
+///
+/// # Errors
+/// Returns an error if the string is truncated, not valid UTF-8 or longer than `max_len`.
+pub fn decode_string(buf: &mut &[u8], max_len: usize) -> Result<String> {
+    let len = read_len(buf)?;
+    // A UTF-16 code unit takes at most 3 bytes in UTF-8
+    if len > max_len.saturating_mul(3) {
+        return Err(anyhow!("String of {} bytes exceeds the limit of {} characters", len, max_len));
+    }
+    let value = std::str::from_utf8(take(buf, len)?).context("String is not valid UTF-8")?;
+    let chars = value.encode_utf16().count();
+    if chars > max_len {
+        return Err(anyhow!("String is {} characters long, the limit is {}", chars, max_len));
+    }
+    Ok(value.to_string())
+}
+
+impl Encode for str {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        encode_string(self, MAX_STRING_LEN, buf)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.as_str().encode(buf)
+    }
+}
+
+impl Decode for String {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        decode_string(buf, MAX_STRING_LEN)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        (**self).encode(buf)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.is_some().encode(buf)?;
+        match self {
+            Some(value) => value.encode(buf),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        if bool::decode(buf)? {
+            Ok(Some(T::decode(buf)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        VarInt::from(self.len() as i32).encode(buf)?;
+        for value in self {
+            value.encode(buf)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.as_slice().encode(buf)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let len = read_len(buf)?;
+        (0..len).map(|_| T::decode(buf)).collect()
+    }
+}
+
+/// Byte array without a length prefix, running to the end of the packet.
+///
+/// Used for the last field of packets like plugin messages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemainingBytes(pub Vec<u8>);
+
+impl Encode for RemainingBytes {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&self.0);
+        Ok(())
+    }
+}
+
+impl Decode for RemainingBytes {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let value = buf.to_vec();
+        *buf = &[];
+        Ok(RemainingBytes(value))
+    }
+}
+
+/// Namespaced identifier, e.g. `minecraft:brand`.
+///
+/// # Example
+/// ```
+/// use mc_ping::types::Identifier;
+///
+/// let id: Identifier = "velocity:player_info".parse()?;
+/// assert_eq!(id.namespace(), "velocity");
+///
+/// // Identifiers without a namespace belong to "minecraft"
+/// assert_eq!("brand".parse::<Identifier>()?.to_string(), "minecraft:brand");
+/// assert!("Bad:Name".parse::<Identifier>().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    namespace: String,
+    path: String,
+}
+
+impl Identifier {
+    /// Creates an identifier from its namespace and path.
+    ///
+    /// # Errors
+    /// Returns an error if the namespace contains characters other than
+    /// `a-z0-9._-`, or the path characters other than `a-z0-9._-/`.
+    pub fn new(namespace: &str, path: &str) -> Result<Identifier> {
+        let valid = |c: char, extra: &str| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c) || extra.contains(c);
+        if namespace.is_empty() || !namespace.chars().all(|c| valid(c, "")) {
+            return Err(anyhow!("Invalid identifier namespace {:?}", namespace));
+        }
+        if path.is_empty() || !path.chars().all(|c| valid(c, "/")) {
+            return Err(anyhow!("Invalid identifier path {:?}", path));
+        }
+        Ok(Identifier {
+            namespace: namespace.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns the namespace, e.g. "minecraft".
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns the path, e.g. "brand".
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl FromStr for Identifier {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Identifier> {
+        match value.split_once(':') {
+            Some((namespace, path)) => Identifier::new(namespace, path),
+            None => Identifier::new(DEFAULT_NAMESPACE, value),
+        }
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+impl Encode for Identifier {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.to_string().encode(buf)
+    }
+}
+
+impl Decode for Identifier {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        String::decode(buf)?.parse()
+    }
+}
+
+/// Block position, packed into 64 bits as x (26 bits), z (26 bits), y (12 bits).
+///
+/// # Example
+/// ```
+/// use mc_ping::types::{Decode, Encode, Position};
+///
+/// let mut buf = Vec::new();
+/// Position::new(-1, 64, 300).encode(&mut buf)?;
+/// assert_eq!(Position::decode(&mut &buf[..])?, Position::new(-1, 64, 300));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Position {
+    /// X coordinate, -33554432 to 33554431.
+    pub x: i32,
+    /// Y coordinate, -2048 to 2047.
+    pub y: i32,
+    /// Z coordinate, -33554432 to 33554431.
+    pub z: i32,
+}
+
+impl Position {
+    /// Creates a position from its coordinates.
+    pub fn new(x: i32, y: i32, z: i32) -> Position {
+        Position { x, y, z }
+    }
+}
+
+impl Encode for Position {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let in_range = |value: i32, bits: u32| (-(1 << (bits - 1))..1 << (bits - 1)).contains(&value);
+        if !in_range(self.x, 26) || !in_range(self.z, 26) || !in_range(self.y, 12) {
+            return Err(anyhow!("Position {:?} is outside the encodable range", self));
+        }
+        let packed = ((self.x as i64 & 0x3FF_FFFF) << 38)
+            | ((self.z as i64 & 0x3FF_FFFF) << 12)
+            | (self.y as i64 & 0xFFF);
+        packed.encode(buf)
+    }
+}
+
+impl Decode for Position {
+    fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let packed = i64::decode(buf)?;
+        // Arithmetic shifts sign-extend each field
+        Ok(Position {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        })
+    }
+}
@@ -8,7 +8,7 @@
 //!
 //! [^mc]: A well-known video game whose servers and clients are able to be built by third-party authors.
 //!
-//! This crate contains two structs for VarInt and VarLong, and four traits for conversations and
+//! This module contains two structs for VarInt and VarLong, and eight traits for conversations and
 //! IO operations on them. You may refer to the paragraphs following to get their usages.
 //!
 //! Algorithms and structures herein are built according to [a wiki.vg page]
@@ -36,6 +36,49 @@
 //! And for the two 'Write' traits, they are implemented for all `W`'s where `W: io::Write` for your
 //! convenience.
 //!
+//! # Async traits
+//!
+//! AsyncVarIntRead, AsyncVarLongRead, AsyncVarIntWrite and AsyncVarLongWrite do the same for
+//! tokio's `AsyncRead + Unpin` and `AsyncWrite + Unpin` streams, so a VarInt can be read straight
+//! off a socket without buffering the packet first:
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use mc_ping::varint::{AsyncVarIntRead, AsyncVarIntWrite, VarInt};
+//!
+//! let mut buf = Vec::new();
+//! buf.write_var_int_async(VarInt::from(25565)).await?;
+//! assert_eq!(buf, vec![0xDD, 0xC7, 0x01]);
+//!
+//! let n = (&buf[..]).read_var_int_async().await?;
+//! assert_eq!(i32::from(n), 25565);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Slices
+//!
+//! `VarInt::decode_slice` and `VarLong::decode_slice` parse a value from the front of a byte slice and report how
+//! many bytes it took, and `encode_slice` writes one into a slice, so framing code can work on buffers
+//! without going through `io::Read`. Decoding rejects encodings longer than 5 (or 10) bytes and values
+//! that do not fit in 32 (or 64) bits; a slice that ends mid-value is reported as
+//! `io::ErrorKind::UnexpectedEof`, so callers can wait for more data.
+//!
+//! ```
+//! use mc_ping::varint::VarInt;
+//!
+//! let mut buf = [0u8; 8];
+//! let len = VarInt::from(-1).encode_slice(&mut buf)?;
+//! assert_eq!(&buf[..len], &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+//!
+//! let (n, read) = VarInt::decode_slice(&buf)?;
+//! assert_eq!((i32::from(n), read), (-1, 5));
+//!
+//! assert!(VarInt::decode_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F]).is_err());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
 //! # How this crate reduces memory usage
 //!
 //! As only VarInt and VarLong struct performs the allocation, firstly we should minimize the space
@@ -43,22 +86,28 @@
 //! instead of something combined with pointers and sizes, the memory usage is reduced to minimal,
 //! which means, the VarInt only uses 5 bytes and VarLong only uses 10.
 //!
-//! When writing to IO, reading from IO or performing type conversations, this crate only allocate
+//! When reading from IO or performing type conversations, this crate only allocate
 //! one `[u8; 1]` array as buffer, and for the Rust's sake, can free it safely even without a GC.
 //! By this way we save more memory in calculating, resulting in more memory able to be used for
 //! network buffers, databases and your following logic code.
 
 #![deny(missing_docs)]
 
+use std::future::Future;
 use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 macro_rules! var_impl {
     ($store_struct: ident, $read_trait: ident, $write_trait: ident, $read_func: ident, $write_func: ident,
-    $conversation_type: ident, $size: expr, $error_too_long: expr) => {
+    $async_read_trait: ident, $async_write_trait: ident, $async_read_func: ident, $async_write_func: ident,
+    $conversation_type: ident, $unsigned_type: ident, $size: expr, $error_too_long: expr, $error_overflow: expr) => {
 
 /// The struct representing a VarInt or VarLong.
+///
+/// Negative values are encoded as their two's complement and always take the maximum size.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct $store_struct {
+    /// Encoded bytes; unused trailing bytes are zero.
     pub inner: [u8; $size]
 }
 
@@ -70,6 +119,82 @@ impl Default for $store_struct {
     }
 }
 
+impl $store_struct {
+    /// Maximum number of bytes an encoded value takes.
+    pub const MAX_SIZE: usize = $size;
+
+    /// Largest value the last possible byte may hold; anything above overflows the integer.
+    const LAST_BYTE_MAX: u8 = (1 << ($unsigned_type::BITS as usize - 7 * ($size - 1))) - 1;
+
+    /// Returns the number of bytes `value` takes when encoded.
+    pub const fn encoded_len(value: $conversation_type) -> usize {
+        let bits = $unsigned_type::BITS - (value as $unsigned_type).leading_zeros();
+        if bits == 0 {
+            1
+        } else {
+            bits.div_ceil(7) as usize
+        }
+    }
+
+    /// Returns the number of bytes this value takes on the wire.
+    pub fn size(&self) -> usize {
+        self.inner.iter()
+            .position(|byte| byte & 0b1000_0000 == 0)
+            .map_or($size, |i| i + 1)
+    }
+
+    /// Returns the encoded bytes, without the unused trailing bytes of `inner`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.inner[..self.size()]
+    }
+
+    /// Decodes a value from the front of `buf` and returns it with the number of bytes it took.
+    ///
+    /// # Errors
+    ///
+    /// Returns `io::ErrorKind::UnexpectedEof` if `buf` ends before the last byte of the value,
+    /// and `io::ErrorKind::InvalidData` if the encoding is too long or the value overflows.
+    pub fn decode_slice(buf: &[u8]) -> io::Result<($store_struct, usize)> {
+        let mut ans = $store_struct::default();
+        for (ptr, &byte) in buf.iter().take($size).enumerate() {
+            ans.inner[ptr] = byte;
+            if byte & 0b1000_0000 == 0 {
+                Self::check_last_byte(ptr, byte)?;
+                return Ok((ans, ptr + 1));
+            }
+        }
+        if buf.len() >= $size {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, $error_too_long));
+        }
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "buffer ends in the middle of a value"))
+    }
+
+    /// Encodes this value into the front of `buf` and returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `io::ErrorKind::WriteZero` if `buf` is shorter than [`size`](Self::size).
+    pub fn encode_slice(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = self.as_bytes();
+        let Some(dst) = buf.get_mut(..bytes.len()) else {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "buffer too small for value"));
+        };
+        dst.copy_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    /// Rejects a final byte at the last position that carries more bits than the integer has.
+    fn check_last_byte(ptr: usize, byte: u8) -> io::Result<()> {
+        if ptr == $size - 1 && byte > Self::LAST_BYTE_MAX {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, $error_overflow));
+        }
+        Ok(())
+    }
+}
+
+/// The Read trait for this VarInt or VarLong struct.
+///
+/// This trait is implemented for all `io::Read`'s.
 pub trait $read_trait {
     /// Reads a VarInt or Varlong from `self`.
     ///
@@ -77,29 +202,25 @@ pub trait $read_trait {
     ///
     /// # Errors
     ///
-    /// If the VarInt or VarLong to read from `self` is too long (is invalid) or this function
-    /// encounters any form of underlying I/O or other error, an error variant will be returned.
+    /// If the VarInt or VarLong to read from `self` is too long or overflows (is invalid) or this
+    /// function encounters any form of underlying I/O or other error, an error variant will be
+    /// returned.
     fn $read_func(&mut self) -> io::Result<$store_struct>;
 }
 
 impl<R> $read_trait for R where R: io::Read {
     fn $read_func(&mut self) -> Result<$store_struct, io::Error> {
-        let mut ans = $store_struct {
-            inner: [0u8; $size]
-        };
-        let mut ptr = 0;
+        let mut ans = $store_struct::default();
         let mut buf = [0u8];
-        loop {
+        for ptr in 0..$size {
             self.read_exact(&mut buf)?;
-            if ptr >= $size {
-                return Err(io::Error::new(io::ErrorKind::InvalidData, $error_too_long));
-            }
             ans.inner[ptr] = buf[0];
-            ptr += 1;
             if buf[0] & 0b1000_0000 == 0 {
+                $store_struct::check_last_byte(ptr, buf[0])?;
                 return Ok(ans);
             }
         }
+        Err(io::Error::new(io::ErrorKind::InvalidData, $error_too_long))
     }
 }
 
@@ -109,8 +230,14 @@ impl<R> $read_trait for R where R: io::Read {
 ///
 /// # Examples
 ///
-/// `Cursor`s implement `io::Write`, thus implement `VarIntWrite` and `VarLongWrite`:
+/// `Vec<u8>`s implement `io::Write`, thus implement `VarIntWrite` and `VarLongWrite`:
+///
+/// ```
+/// use mc_ping::varint::{VarInt, VarIntWrite};
 ///
+/// let mut buf = Vec::new();
+/// buf.write_var_int(VarInt::from(300)).unwrap();
+/// assert_eq!(buf, vec![0xAC, 0x02]);
 /// ```
 pub trait $write_trait {
     /// Writes a VarInt or Varlong to `self`.
@@ -126,83 +253,93 @@ pub trait $write_trait {
 
 impl<W> $write_trait for W where W: io::Write {
     fn $write_func(&mut self, n: $store_struct) -> io::Result<()> {
-        let mut buf = [0x00];
-        let mut ptr = 0;
-        loop {
-            if n.inner[ptr] == 0 {
-                break;
-            }
-            buf[0] = n.inner[ptr];
-            self.write_all(&buf)?;
-            ptr += 1;
-            if ptr >= $size {
-                break;
+        self.write_all(n.as_bytes())
+    }
+}
+
+/// The async Read trait for this VarInt or VarLong struct.
+///
+/// This trait is implemented for all tokio `AsyncRead + Unpin`'s.
+pub trait $async_read_trait {
+    /// Reads a VarInt or Varlong from `self`, one byte at a time.
+    ///
+    /// # Errors
+    ///
+    /// If the VarInt or VarLong to read from `self` is too long or overflows (is invalid) or this
+    /// function encounters any form of underlying I/O or other error, an error variant will be
+    /// returned.
+    fn $async_read_func(&mut self) -> impl Future<Output = io::Result<$store_struct>> + Send;
+}
+
+impl<R> $async_read_trait for R where R: AsyncRead + Unpin + Send {
+    async fn $async_read_func(&mut self) -> io::Result<$store_struct> {
+        let mut ans = $store_struct::default();
+        for ptr in 0..$size {
+            let byte = self.read_u8().await?;
+            ans.inner[ptr] = byte;
+            if byte & 0b1000_0000 == 0 {
+                $store_struct::check_last_byte(ptr, byte)?;
+                return Ok(ans);
             }
         }
-        // If no bytes written, that is, the $store_struct is equal to 0
-        if ptr == 0 {
-            // At that time, `buf` is still [0x00], let's write it
-            self.write_all(&buf)?;
-        }
-        Ok(())
+        Err(io::Error::new(io::ErrorKind::InvalidData, $error_too_long))
+    }
+}
+
+/// The async Write trait for this VarInt or VarLong struct.
+///
+/// This trait is implemented for all tokio `AsyncWrite + Unpin`'s.
+pub trait $async_write_trait {
+    /// Writes a VarInt or Varlong to `self` with a single write.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of underlying I/O or other error, an error variant
+    /// will be returned.
+    fn $async_write_func(&mut self, n: $store_struct) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+impl<W> $async_write_trait for W where W: AsyncWrite + Unpin + Send {
+    async fn $async_write_func(&mut self, n: $store_struct) -> io::Result<()> {
+        self.write_all(n.as_bytes()).await
     }
 }
 
 impl From<$store_struct> for $conversation_type {
     fn from(v: $store_struct) -> Self {
-        let mut ans = 0 as Self;
-        let mut ptr = 0;
-        loop {
-            let value = $conversation_type::from(v.inner[ptr] & 0b0111_1111);
-            ans |= value << (7 * ptr as Self);
-            if v.inner[ptr] & 0b1000_0000 == 0 {
-                return ans;
-            }
-            ptr += 1;
+        let mut ans: $unsigned_type = 0;
+        for (ptr, byte) in v.as_bytes().iter().enumerate() {
+            // Bits past the integer width are dropped, `decode_slice` rejects them beforehand
+            ans |= $unsigned_type::from(byte & 0b0111_1111) << (7 * ptr);
         }
+        ans as Self
     }
 }
 
 impl From<$conversation_type> for $store_struct {
     fn from(n: $conversation_type) -> Self {
-        let mut ans = $store_struct {
-            inner: [0u8; $size]
-        };
-        let mut n = n;
+        let mut ans = $store_struct::default();
+        // Shifting the unsigned form is a logical shift, so negative values terminate
+        let mut n = n as $unsigned_type;
         let mut ptr = 0;
         loop {
-            let mut tmp = (n & 0b0111_1111) as u8;
-            // There isn't a logical right-shift operator in Rust
-            n = (n >> 7) & ($conversation_type::max_value() >> 6);
-            if n != 0 {
-                tmp |= 0b1000_0000;
+            let byte = (n & 0b0111_1111) as u8;
+            n >>= 7;
+            if n == 0 {
+                ans.inner[ptr] = byte;
+                return ans;
             }
-            ans.inner[ptr] = tmp;
+            ans.inner[ptr] = byte | 0b1000_0000;
             ptr += 1;
-            if n == 0 || ptr >= $size {
-                break;
-            }
         }
-        ans
     }
 }
     };
 }
 
-impl VarInt {
-    pub fn size(&self) -> usize {
-        for i in 0..self.inner.len() {
-            if self.inner[i] & 0x80 == 0 {
-                return i + 1;
-            }
-        }
-        self.inner.len()
-    }
-}
-
-
 var_impl!(VarInt, VarIntRead, VarIntWrite, read_var_int, write_var_int,
-            i32, 5, "varint too long (length > 5)");
+            AsyncVarIntRead, AsyncVarIntWrite, read_var_int_async, write_var_int_async,
+            i32, u32, 5, "varint too long (length > 5)", "varint too big (value > 32 bits)");
 var_impl!(VarLong, VarLongRead, VarLongWrite, read_var_long, write_var_long,
-            i64, 10, "varlong too long (length > 10)");
-
+            AsyncVarLongRead, AsyncVarLongWrite, read_var_long_async, write_var_long_async,
+            i64, u64, 10, "varlong too long (length > 10)", "varlong too big (value > 64 bits)");